@@ -0,0 +1,293 @@
+//! Beam search over the moves of the empty square, scored by the largest tree on the board.
+
+use rand::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use crate::budget::Budget;
+use crate::observer::EventKind;
+use crate::params::Params;
+use crate::solver::Solver;
+use crate::{best_prefix, Input, Output};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct Board {
+    board_size: usize,
+    board_list: Vec<u8>,
+    empty_block_area: (usize, usize),
+}
+
+#[allow(dead_code)]
+impl Board {
+    const DCHARS: [char; 4] = ['L', 'U', 'R', 'D'];
+    const DH: [usize; 4] = [0, !0, 0, 1];
+    const DW: [usize; 4] = [!0, 0, 1, 0]; //LURD
+    fn new(board_size: usize, board_list: Vec<u8>) -> Board {
+        let mut empty_block_area = (0, 0);
+        for h in 0..board_size {
+            for w in 0..board_size {
+                if board_list[h * board_size + w] == 0 {
+                    empty_block_area = (h, w);
+                }
+            }
+        }
+        Board {
+            board_size,
+            board_list,
+            empty_block_area,
+        }
+    }
+
+    fn from_input(input: &Input) -> Board {
+        Board::new(input.n, input.tiles.iter().flatten().map(|&tile| tile as u8).collect())
+    }
+
+    fn move_empty_block(&mut self, didx: usize) -> bool {
+        let (th, tw) = (
+            self.empty_block_area.0.wrapping_add(Self::DH[didx]),
+            self.empty_block_area.1.wrapping_add(Self::DW[didx]),
+        );
+        if th >= self.board_size || tw >= self.board_size {
+            return false;
+        }
+        self.swap(self.empty_block_area, (th, tw));
+        self.empty_block_area = (th, tw);
+        true
+    }
+
+    fn move_left(&mut self) -> bool {
+        self.move_empty_block(0)
+    }
+
+    fn move_up(&mut self) -> bool {
+        self.move_empty_block(1)
+    }
+
+    fn move_right(&mut self) -> bool {
+        self.move_empty_block(2)
+    }
+
+    fn move_down(&mut self) -> bool {
+        self.move_empty_block(3)
+    }
+
+    fn swap(&mut self, (h1, w1): (usize, usize), (h2, w2): (usize, usize)) {
+        self.board_list
+            .swap(h1 * self.board_size + w1, h2 * self.board_size + w2);
+    }
+
+    fn replace(&mut self, h: usize, w: usize, value: u8) {
+        self.board_list[h * self.board_size + w] = value;
+    }
+
+    fn get(&self, h: usize, w: usize) -> u8 {
+        self.board_list[h * self.board_size + w]
+    }
+
+    fn print_board(&self) {
+        for h in 0..self.board_size {
+            let mut bytes = vec![];
+            for w in 0..self.board_size {
+                bytes.push(format!("{:x}", self.board_list[h * self.board_size + w]));
+            }
+            println!("{}", bytes.into_iter().collect::<String>());
+        }
+    }
+}
+
+struct Node {
+    board: Board,
+    score: i32
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score.cmp(&other.score)
+    }
+}
+
+pub struct BeamSearch<'a> {
+    pub params: &'a Params,
+}
+
+/// Searches from the board the best prefix of `warm_start` leaves, and answers with that prefix
+/// followed by the best prefix of the deepest route, as the moves at the end of a beam that ran
+/// out of time may only lose score.
+impl Solver for BeamSearch<'_> {
+    fn solve_from(&self, input: &Input, mut warm_start: Output, budget: &Budget, rng: &mut impl Rng) -> Output {
+        warm_start.truncate(best_prefix(input, &warm_start));
+        let mut board = Board::from_input(input);
+        for &dchar in &warm_start {
+            board.move_empty_block(Board::DCHARS.iter().position(|&c| c == dchar).unwrap());
+        }
+        let mut solution = warm_start;
+        solution.extend(beam_search(input.T - solution.len(), board, self.params, budget, rng));
+        solution.truncate(best_prefix(input, &solution));
+        solution
+    }
+}
+
+fn beam_search(max_iter: usize, init_board: Board, params: &Params, budget: &Budget, rng: &mut impl Rng) -> Vec<char> {
+    let beam_width = params.beam_width(init_board.board_size);
+    let seed = params.seed;
+    let mut best_score = calc_score(&init_board, seed);
+    let mut best_board = init_board.clone();
+    let mut record = HashMap::new();
+    let mut que = vec![];
+    que.push(Node {
+        board: init_board.clone(),
+        score: calc_score(&init_board, seed)
+    });
+    record.insert(init_board, 'S');
+    let mut expanded = 0;
+    'mainloop: for depth in 0..max_iter {
+        let mut next_que = vec![];
+        while let Some(mut node) = que.pop() {
+            budget.tick();
+            if budget.is_over() {
+                break 'mainloop
+            }
+            expanded += 1;
+            if node.score > best_score {
+                best_score = node.score;
+                best_board = node.board.clone();
+                budget.report(EventKind::Best, "beam search", expanded, best_score as f64, depth);
+            }
+            // up
+            if node.board.move_up() {
+                if !record.contains_key(&node.board) {
+                    record.insert(node.board.clone(), 'U');
+                    next_que.push(Node {
+                        board: node.board.clone(),
+                        score: calc_score(&node.board, seed) + rng.gen_range(0, 1000)
+                    });
+                }
+                node.board.move_down();
+            }
+            // down
+            if node.board.move_down() {
+                if !record.contains_key(&node.board) {
+                    record.insert(node.board.clone(), 'D');
+                    next_que.push(Node {
+                        board: node.board.clone(),
+                        score: calc_score(&node.board, seed) + rng.gen_range(0, 1000)
+                    });
+                }
+                node.board.move_up();
+            }
+            // left
+            if node.board.move_left() {
+                if !record.contains_key(&node.board) {
+                    record.insert(node.board.clone(), 'L');
+                    next_que.push(Node {
+                        board: node.board.clone(),
+                        score: calc_score(&node.board, seed) + rng.gen_range(0, 1000)
+                    });
+                }
+                node.board.move_right();
+            }
+            // right
+            if node.board.move_right() {
+                if !record.contains_key(&node.board) {
+                    record.insert(node.board.clone(), 'R');
+                    next_que.push(Node {
+                        board: node.board.clone(),
+                        score: calc_score(&node.board, seed) + rng.gen_range(0, 1000)
+                    });
+                }
+                node.board.move_left();
+            }
+        }
+        next_que.sort();
+        for _ in 0..beam_width {
+            if let Some(v) = next_que.pop() {
+                que.push(v);
+            }
+        }
+    }
+    let mut best_solution = vec![];
+    while let Some(&dchar) = record.get(&best_board) {
+        if dchar == 'S' {
+            break;
+        }
+        best_solution.push(dchar);
+        match dchar {
+            'U' => {
+                best_board.move_down();
+            }
+            'D' => {
+                best_board.move_up();
+            }
+            'L' => {
+                best_board.move_right();
+            }
+            'R' => {
+                best_board.move_left();
+            }
+            'S' => {
+                break;
+            }
+            _ => unreachable!(),
+        }
+    }
+    eprintln!("score = {}", best_score);
+    budget.report(EventKind::Done, "beam search", expanded, best_score as f64, best_solution.len());
+    best_solution.reverse();
+    best_solution
+}
+
+fn calc_score(board: &Board, seed: u128) -> i32 {
+    let mut rng = rand_pcg::Pcg64Mcg::new(seed);
+    let board_size = board.board_size;
+    let mut score = 0;
+    let mut seen = vec![vec![false; board_size]; board_size];
+    let mut que = VecDeque::new();
+    let mut max_tree_size = 0;
+    for h_st in 0..board_size {
+        for w_st in 0..board_size {
+            if !seen[h_st][w_st] {
+                let mut tree_size = 0;
+                seen[h_st][w_st] = true;
+                que.push_back((h_st, w_st));
+                while let Some((h_now, w_now)) = que.pop_front() {
+                    tree_size += 1;
+                    for didx in 0..4 {
+                        if ((board.get(h_now, w_now) >> didx) & 1) == 1 {
+                            let (h_to, w_to) = (
+                                h_now.wrapping_add(Board::DH[didx]),
+                                w_now.wrapping_add(Board::DW[didx]),
+                            );
+                            if h_to >= board_size || w_to >= board_size {
+                                score -= 1000;
+                                continue;
+                            }
+                            if ((board.get(h_to, w_to) >> ((didx + 2) % 4)) & 1) == 1
+                                && !seen[h_to][w_to]
+                            {
+                                seen[h_to][w_to] = true;
+                                que.push_back((h_to, w_to));
+                            }
+                        }
+                    }
+                }
+                max_tree_size = i32::max(max_tree_size, tree_size);
+            }
+        }
+    }
+    score += (5e5 * max_tree_size as f32 / (board_size * board_size - 1) as f32).round() as i32;
+    score + rng.gen_range(0, 1000)
+}
+
+
@@ -2,10 +2,14 @@ use std::io::prelude::*;
 use AHC011::*;
 
 fn main() {
-	if std::env::args().len() != 2 {
-		eprintln!("Usage: {} seeds.txt", std::env::args().nth(0).unwrap());
+	if std::env::args().len() != 2 && std::env::args().len() != 3 {
+		eprintln!("Usage: {} seeds.txt [board size]", std::env::args().next().unwrap());
 		return;
 	}
+	let n = std::env::args().nth(2).map(|n| match n.parse::<usize>() {
+		Ok(n) if n >= 2 => n,
+		_ => { eprintln!("invalid board size: {}", n); std::process::exit(1) }
+	});
 	if !std::path::Path::new("in").exists() {
 		std::fs::create_dir("in").unwrap();
 	}
@@ -16,11 +20,14 @@ fn main() {
 	for line in f.lines() {
 		let line = line.unwrap();
 		let line = line.trim();
-		if line.len() == 0 {
+		if line.is_empty() {
 			continue;
 		}
 		let seed = line.parse::<u64>().unwrap_or_else(|_| { eprintln!("parse failed: {}", line); std::process::exit(1) });
-		let input = gen(seed);
+		let input = match n {
+			Some(n) => gen_with_size(seed, n),
+			None => gen(seed),
+		};
 		let mut w = std::io::BufWriter::new(std::fs::File::create(format!("in/{:04}.txt", id)).unwrap());
 		write!(w, "{}", input).unwrap();
 		id += 1;
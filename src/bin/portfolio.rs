@@ -0,0 +1,126 @@
+//! Races the solvers against each other under one time budget and prints the best legal answer.
+//!
+//! With several cores every solver runs on its own thread for the whole budget. On a single core
+//! (or with `--sequential`) each solver first gets a short probe, and the rest of the budget goes
+//! to whichever probe scored best, starting from its answer.
+//!
+//! Given an answer file, such as a best-known out/NNNN.txt, every solver starts from it and it
+//! competes as an answer of its own.
+
+use AHC011::budget::Budget;
+use AHC011::beam_search::BeamSearch;
+use AHC011::move_annealing::RerouteAnnealing;
+use AHC011::params::Params;
+use AHC011::solver::Solver;
+use AHC011::*;
+
+
+/// Share of the budget the sequential mode spends probing all solvers.
+const PROBE_SHARE: f64 = 0.3;
+
+type Solve = fn(&Input, &Params, Vec<char>, &Budget) -> Vec<char>;
+
+const SOLVERS: [(&str, Solve); 3] = [
+	("greedy", |input, params, warm_start, budget| greedy::pipeline(params, false).solve_from(input, warm_start, budget, &mut rng(params))),
+	("beam search", |input, params, warm_start, budget| BeamSearch { params }.solve_from(input, warm_start, budget, &mut rng(params))),
+	("annealing", |input, params, warm_start, budget| RerouteAnnealing { params }.solve_from(input, warm_start, budget, &mut rng(params))),
+];
+
+fn rng(params: &Params) -> rand_pcg::Pcg64Mcg {
+	rand_pcg::Pcg64Mcg::new(params.seed)
+}
+
+struct Candidate {
+	solver: usize,
+	score: i64,
+	out: Vec<char>,
+}
+
+/// Replays `out` with `Sim`, cutting it to its best legal prefix if any move is illegal or there
+/// are too many of them, and otherwise to its best prefix.
+fn evaluate(input: &Input, solver: usize, out: Vec<char>) -> Candidate {
+	let mut out = guard_answer(input, SOLVERS[solver].0, out, &[]);
+	out.truncate(best_prefix(input, &out));
+	let score = compute_score(input, &out).0;
+	eprintln!("{}: score = {} ({} moves)", SOLVERS[solver].0, score, out.len());
+	Candidate { solver, score, out }
+}
+
+/// Runs `solver` from `warm_start` until `budget` is over and evaluates its answer.
+fn run(input: &Input, params: &Params, solver: usize, warm_start: &[char], budget: &Budget) -> Candidate {
+	evaluate(input, solver, SOLVERS[solver].1(input, params, warm_start.to_vec(), budget))
+}
+
+fn race_parallel(input: &Input, params: &Params, warm_start: &[char], budget: &Budget) -> Vec<Candidate> {
+	std::thread::scope(|scope| {
+		let handles = (0..SOLVERS.len()).map(|solver| {
+			let budget = budget.fork();
+			scope.spawn(move || run(input, params, solver, warm_start, &budget))
+		}).collect::<Vec<_>>();
+		// A solver that panics only loses its own candidate.
+		handles.into_iter().filter_map(|handle| handle.join().ok()).collect()
+	})
+}
+
+fn race_sequential(input: &Input, params: &Params, warm_start: &[char], budget: &Budget) -> Vec<Candidate> {
+	// A probe lasts at least one restart of the greedy pipeline, which otherwise answers with
+	// nothing but an unpolished route, unless that leaves no time for the other probes.
+	let restart = (params.search_time(input.n) + params.polish_time) as f64;
+	let share = budget.remaining() / SOLVERS.len() as f64;
+	let probe = f64::min(f64::max(share * PROBE_SHARE, restart), share);
+	let mut candidates = (0..SOLVERS.len()).map(|solver| run(input, params, solver, warm_start, &budget.slice(probe))).collect::<Vec<_>>();
+	if let Some(leader) = candidates.iter().max_by_key(|candidate| candidate.score) {
+		if !budget.is_over() {
+			eprintln!("{} gets the remaining {:.2}s", SOLVERS[leader.solver].0, budget.remaining());
+			// The leader goes on from its probe's answer rather than starting over.
+			let (leader, out) = (leader.solver, leader.out.clone());
+			candidates.push(run(input, params, leader, &out, budget));
+		}
+	}
+	candidates
+}
+
+fn main() {
+	let mut sequential = std::thread::available_parallelism().map_or(1, |n| n.get()) < 2;
+	let (mut telemetry, mut warm_start_path) = (None, None);
+	let (params, args) = Params::default().from_args();
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--sequential" => sequential = true,
+			"--parallel" => sequential = false,
+			"--telemetry" => telemetry = args.next(),
+			_ if !arg.starts_with("--") && warm_start_path.is_none() => warm_start_path = Some(arg),
+			_ => {
+				eprintln!("Usage: portfolio [--sequential | --parallel] [--telemetry FILE] [--params FILE] [--<key>=<value>...] [ANSWER] < input");
+				std::process::exit(1);
+			}
+		}
+	}
+	eprint!("{}", params);
+	let input = std::io::read_to_string(std::io::stdin()).unwrap();
+	let input = parse_input(&input);
+	let warm_start = match warm_start_path {
+		Some(path) => read_answer(&input, &path).unwrap_or_else(|err| {
+			eprintln!("ignoring warm start: {}", err);
+			vec![]
+		}),
+		None => vec![],
+	};
+	let budget = params.budget().with_telemetry(telemetry.as_deref());
+	let candidates = if sequential {
+		race_sequential(&input, &params, &warm_start, &budget)
+	} else {
+		race_parallel(&input, &params, &warm_start, &budget)
+	};
+	// The warm start, or else doing nothing, is legal, so there is an answer even if every
+	// solver panics.
+	let mut best = (compute_score(&input, &warm_start).0, if warm_start.is_empty() { "no solver" } else { "warm start" }, warm_start);
+	for candidate in candidates {
+		if candidate.score > best.0 {
+			best = (candidate.score, SOLVERS[candidate.solver].0, candidate.out);
+		}
+	}
+	eprintln!("best: {} with score {}", best.1, best.0);
+	println!("{}", best.2.iter().collect::<String>());
+}
@@ -0,0 +1,249 @@
+//! Tunes the parameters of a solver for each board size with successive halving.
+//!
+//! The space file has `key=lo..hi` lines. Every candidate draws each key uniformly from its range,
+//! as an integer if both bounds are integers. For a table such as `search_time` the range is for
+//! the entry of the board size being tuned. The first candidate is the defaults.
+//!
+//! All candidates run on the first input of a board size, the better half of them on the first
+//! two, and so on until one is left or the inputs run out. With `--random` every candidate runs on
+//! all inputs instead. The winners are printed as a params file, with the tables merged.
+//!
+//! With `--telemetry DIR` the progress events of every run are written to `DIR/N_C_I.jsonl`, for
+//! candidate `C` on input `I` of board size `N`.
+
+use rand::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use AHC011::budget::Budget;
+use AHC011::beam_search::BeamSearch;
+use AHC011::move_annealing::RerouteAnnealing;
+use AHC011::params::{Params, TABLES};
+use AHC011::solver::{Pipeline, Solver};
+use AHC011::*;
+
+
+const CANDIDATES: usize = 16;
+
+type Solve = fn(&Input, &Params, Vec<char>, &Budget) -> Vec<char>;
+
+const SOLVERS: [(&str, Solve); 4] = [
+	("greedy", |input, params, warm_start, budget| greedy::pipeline(params, false).solve_from(input, warm_start, budget, &mut rng(params))),
+	("beam", |input, params, warm_start, budget| BeamSearch { params }.solve_from(input, warm_start, budget, &mut rng(params))),
+	("annealing", |input, params, warm_start, budget| RerouteAnnealing { params }.solve_from(input, warm_start, budget, &mut rng(params))),
+	("greedy+reroute", greedy_reroute),
+];
+
+fn rng(params: &Params) -> rand_pcg::Pcg64Mcg {
+	rand_pcg::Pcg64Mcg::new(params.seed)
+}
+
+/// The greedy pipeline with its route polished by the move annealing instead.
+fn greedy_reroute(input: &Input, params: &Params, warm_start: Vec<char>, budget: &Budget) -> Vec<char> {
+	let pipeline = Pipeline {
+		search: greedy::TileSwapSearch { params },
+		router: greedy::GreedyRouter { debug: false },
+		polisher: RerouteAnnealing { params },
+	};
+	pipeline.solve_from(input, warm_start, budget, &mut rng(params))
+}
+
+struct Range {
+	key: String,
+	lo: f64,
+	hi: f64,
+	integer: bool,
+}
+
+fn read_space(path: &str) -> Result<Vec<Range>, String> {
+	let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+	let mut space = vec![];
+	for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+		let invalid = || format!("expected key=lo..hi: {}", line);
+		let (key, range) = line.split_once('=').ok_or_else(invalid)?;
+		let (lo, hi) = range.split_once("..").ok_or_else(invalid)?;
+		let key = key.trim().replace('-', "_");
+		if Params::default().get(&key).is_none() {
+			return Err(format!("unknown parameter: {}", key));
+		}
+		let integer = lo.trim().parse::<i64>().is_ok() && hi.trim().parse::<i64>().is_ok();
+		let (lo, hi) = (lo.trim().parse().map_err(|_| invalid())?, hi.trim().parse().map_err(|_| invalid())?);
+		space.push(Range { key, lo, hi, integer });
+	}
+	Ok(space)
+}
+
+/// The value of `key` for board size `n`.
+fn value(params: &Params, key: &str, n: usize) -> String {
+	let value = params.get(key).unwrap();
+	if TABLES.contains(&key) {
+		value.split(',').nth(n).unwrap_or("").to_owned()
+	} else {
+		value
+	}
+}
+
+fn set_value(params: &mut Params, key: &str, n: usize, value: &str) -> Result<(), String> {
+	if !TABLES.contains(&key) {
+		return params.set(key, value);
+	}
+	let mut table = params.get(key).unwrap().split(',').map(str::to_owned).collect::<Vec<_>>();
+	if n >= table.len() {
+		return Err(format!("{} has no entry for n = {}", key, n));
+	}
+	table[n] = value.to_owned();
+	params.set(key, &table.join(","))
+}
+
+fn sample<R: Rng>(base: &Params, space: &[Range], n: usize, rng: &mut R) -> Result<Params, String> {
+	let mut params = base.clone();
+	for range in space {
+		let value = if range.lo >= range.hi {
+			range.lo.to_string()
+		} else if range.integer {
+			rng.gen_range(range.lo as i64, range.hi as i64 + 1).to_string()
+		} else {
+			rng.gen_range(range.lo, range.hi).to_string()
+		};
+		set_value(&mut params, &range.key, n, &value)?;
+	}
+	Ok(params)
+}
+
+/// Scores every pair of `jobs` on `threads` threads, writing the telemetry of a job to its file if
+/// it has one. An illegal answer scores 0.
+fn run_jobs(solve: Solve, jobs: &[(&Params, &Input, Option<String>)], threads: usize) -> Vec<i64> {
+	let next = AtomicUsize::new(0);
+	let scores = Mutex::new(vec![0; jobs.len()]);
+	std::thread::scope(|scope| {
+		for _ in 0..threads {
+			scope.spawn(|| loop {
+				let k = next.fetch_add(1, Ordering::Relaxed);
+				if k >= jobs.len() {
+					break;
+				}
+				let (params, input, telemetry) = &jobs[k];
+				let budget = params.budget().with_telemetry(telemetry.as_deref());
+				let score = compute_score(input, &solve(input, params, vec![], &budget)).0;
+				scores.lock().unwrap()[k] = score;
+			});
+		}
+	});
+	scores.into_inner().unwrap()
+}
+
+struct Candidate {
+	id: usize,
+	params: Params,
+	/// Sum of the scores on the first `runs` inputs.
+	total: i64,
+	runs: usize,
+}
+
+impl Candidate {
+	fn mean(&self) -> f64 {
+		self.total as f64 / self.runs.max(1) as f64
+	}
+}
+
+/// Returns the best of `candidates` on `inputs`, all of the same board size.
+fn halve(
+	solve: Solve,
+	mut candidates: Vec<Candidate>,
+	inputs: &[Input],
+	random: bool,
+	threads: usize,
+	telemetry: Option<&str>,
+) -> Candidate {
+	let mut done = 0;
+	while candidates.len() > 1 && done < inputs.len() {
+		let next = if random { inputs.len() } else { (2 * done).clamp(1, inputs.len()) };
+		let jobs = candidates.iter().flat_map(|candidate| {
+			(done..next).map(move |k| {
+				let path = telemetry.map(|dir| format!("{}/{}_{}_{}.jsonl", dir, inputs[k].n, candidate.id, k));
+				(&candidate.params, &inputs[k], path)
+			})
+		}).collect::<Vec<_>>();
+		let scores = run_jobs(solve, &jobs, threads);
+		for (candidate, scores) in candidates.iter_mut().zip(scores.chunks(next - done)) {
+			candidate.total += scores.iter().sum::<i64>();
+			candidate.runs = next;
+		}
+		done = next;
+		candidates.sort_by(|a, b| b.mean().total_cmp(&a.mean()));
+		eprintln!("n = {}: {} candidates on {} inputs, best mean {:.0}", inputs[0].n, candidates.len(), done, candidates[0].mean());
+		candidates.truncate(candidates.len().div_ceil(2));
+	}
+	candidates.swap_remove(0)
+}
+
+fn main() {
+	let (mut base, args) = Params::default().from_args();
+	let (mut solver, mut candidates, mut threads, mut random) = (0, CANDIDATES, std::thread::available_parallelism().map_or(1, |n| n.get()), false);
+	let (mut files, mut telemetry) = (vec![], None);
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--solver" => {
+				let name = args.next().unwrap_or_default();
+				solver = SOLVERS.iter().position(|s| s.0 == name).unwrap_or_else(|| {
+					eprintln!("unknown solver: {}", name);
+					std::process::exit(1)
+				});
+			}
+			"--candidates" => candidates = args.next().and_then(|n| n.parse().ok()).unwrap_or(CANDIDATES).max(1),
+			"--threads" => threads = args.next().and_then(|n| n.parse().ok()).unwrap_or(threads).max(1),
+			"--random" => random = true,
+			"--telemetry" => telemetry = args.next(),
+			_ => files.push(arg),
+		}
+	}
+	if files.len() < 2 {
+		eprintln!("Usage: tune SPACE INPUT... [--solver greedy|beam|annealing|greedy+reroute] [--candidates N] [--threads N] [--random] [--telemetry DIR] [--params FILE] [--<key>=<value>...]");
+		std::process::exit(1);
+	}
+	eprint!("{}", base);
+	let space = read_space(&files[0]).unwrap_or_else(|err| {
+		eprintln!("{}", err);
+		std::process::exit(1)
+	});
+	let mut by_size = std::collections::BTreeMap::<usize, Vec<Input>>::new();
+	for file in &files[1..] {
+		let input = std::fs::read_to_string(file).unwrap_or_else(|_| {
+			eprintln!("no such file: {}", file);
+			std::process::exit(1)
+		});
+		let input = parse_input(&input);
+		by_size.entry(input.n).or_default().push(input);
+	}
+	base.extend_tables(*by_size.keys().last().unwrap());
+	let mut rng = rand_pcg::Pcg64Mcg::new(base.seed);
+	let mut tuned = base.clone();
+	let mut rows = vec![];
+	for (&n, inputs) in &by_size {
+		let mut pool = vec![Candidate { id: 0, params: base.clone(), total: 0, runs: 0 }];
+		for id in 1..candidates {
+			let params = sample(&base, &space, n, &mut rng).unwrap_or_else(|err| {
+				eprintln!("{}", err);
+				std::process::exit(1)
+			});
+			pool.push(Candidate { id, params, total: 0, runs: 0 });
+		}
+		let best = halve(SOLVERS[solver].1, pool, inputs, random, threads, telemetry.as_deref());
+		for range in space.iter().filter(|range| TABLES.contains(&range.key.as_str())) {
+			set_value(&mut tuned, &range.key, n, &value(&best.params, &range.key, n)).unwrap();
+		}
+		rows.push((n, best.mean(), best.runs, best.params));
+	}
+	let keys = space.iter().map(|range| range.key.as_str()).filter(|key| !TABLES.contains(key)).collect::<Vec<_>>();
+	println!("# best {} parameters by board size", SOLVERS[solver].0);
+	println!("# n mean runs {}", keys.join(" "));
+	for (n, mean, runs, params) in &rows {
+		let values = keys.iter().map(|key| value(params, key, *n)).collect::<Vec<_>>();
+		println!("# {} {:.0} {} {}", n, mean, runs, values.join(" "));
+	}
+	for key in TABLES {
+		if space.iter().any(|range| range.key == key) {
+			println!("{}={}", key, tuned.get(key).unwrap());
+		}
+	}
+}
@@ -1,26 +1,32 @@
 #![allow(non_snake_case)]
 
+use AHC011::trace::Trace;
 use AHC011::*;
 
 fn main() {
-	if std::env::args().len() != 3 {
-		eprintln!("Usage: {} <input> <output>", std::env::args().nth(0).unwrap());
+	if std::env::args().len() != 3 && std::env::args().len() != 4 {
+		eprintln!("Usage: {} <input> <output> [<trace>]", std::env::args().next().unwrap());
 		return;
 	}
 	let in_file = std::env::args().nth(1).unwrap();
 	let out_file = std::env::args().nth(2).unwrap();
 	let input = std::fs::read_to_string(&in_file).unwrap_or_else(|_| { eprintln!("no such file: {}", in_file); std::process::exit(1) });
 	let output = std::fs::read_to_string(&out_file).unwrap_or_else(|_| { eprintln!("no such file: {}", out_file); std::process::exit(1) });
+	let trace = std::env::args().nth(3).map(|trace_file| Trace::read(&trace_file).unwrap_or_else(|err| { eprintln!("{}", err); std::process::exit(1) }));
 	let input = parse_input(&input);
 	let out = parse_output(&input, &output);
-	let (score, err, svg) = match out {
-		Ok(out) => vis(&input, &out, true),
-		Err(err) => (0, err, String::new())
+	let (score, err, svg) = match &out {
+		Ok(out) => vis(&input, out, true),
+		Err(err) => (0, err.clone(), String::new())
 	};
 	println!("Score = {}", score);
-	if err.len() > 0 {
+	if !err.is_empty() {
 		println!("{}", err);
 	}
-	let vis = format!("<html><body>{}</body></html>", svg);
+	let trace = match (&trace, &out) {
+		(Some(trace), Ok(out)) => vis_trace(&input, out, trace),
+		_ => String::new(),
+	};
+	let vis = format!("<html><body>{}{}</body></html>", svg, trace);
 	std::fs::write("vis.html", &vis).unwrap();
 }
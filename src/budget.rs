@@ -0,0 +1,115 @@
+//! Time budgets the solvers stop on.
+//!
+//! A budget is measured either in wall-clock seconds or in evaluations, which the solvers count
+//! with `tick`. With counted evaluations a second is a fixed number of them, so a run with a given
+//! seed and budget is reproducible bit for bit regardless of machine load.
+//!
+//! A budget also carries the observer the stages report their progress to.
+
+use crate::observer::{Event, EventKind, JsonLines, Observer};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Clones share the clock and the observer, so a stage given a clone or a `slice` uses up the
+/// budget of its caller.
+#[derive(Clone)]
+pub struct Budget {
+	/// Evaluations per second, or 0 for wall-clock time.
+	evals_per_sec: u64,
+	start: std::time::Instant,
+	evals: Arc<AtomicU64>,
+	/// Elapsed seconds at which the budget runs out.
+	end: f64,
+	observer: Option<Arc<dyn Observer>>,
+}
+
+impl Budget {
+	/// A budget of `seconds`, each of them `evals_per_sec` evaluations unless that is 0.
+	pub fn new(seconds: f64, evals_per_sec: u64) -> Budget {
+		Budget {
+			evals_per_sec,
+			start: std::time::Instant::now(),
+			evals: Arc::new(AtomicU64::new(0)),
+			end: seconds,
+			observer: None,
+		}
+	}
+
+	pub fn with_observer(self, observer: Arc<dyn Observer>) -> Budget {
+		Budget { observer: Some(observer), ..self }
+	}
+
+	/// Reports to a `JsonLines` file at `path`, if given. A file that cannot be created is only
+	/// warned about.
+	pub fn with_telemetry(self, path: Option<&str>) -> Budget {
+		let Some(path) = path else {
+			return self;
+		};
+		match JsonLines::create(path) {
+			Ok(sink) => self.with_observer(Arc::new(sink)),
+			Err(err) => {
+				eprintln!("no telemetry: {}: {}", path, err);
+				self
+			}
+		}
+	}
+
+	/// Passes an event of `stage`, stamped with the clock, to the observer.
+	pub fn report(&self, kind: EventKind, stage: &str, iteration: usize, score: f64, moves: usize) {
+		if let Some(observer) = &self.observer {
+			observer.observe(&Event { time: self.elapsed(), kind, stage, iteration, score, moves });
+		}
+	}
+
+	pub fn is_counted(&self) -> bool {
+		self.evals_per_sec > 0
+	}
+
+	/// Counts one evaluation.
+	pub fn tick(&self) {
+		self.tick_n(1);
+	}
+
+	pub fn tick_n(&self, evals: u64) {
+		if self.is_counted() {
+			self.evals.fetch_add(evals, Ordering::Relaxed);
+		}
+	}
+
+	/// Seconds since the clock started.
+	pub fn elapsed(&self) -> f64 {
+		if self.is_counted() {
+			self.evals.load(Ordering::Relaxed) as f64 / self.evals_per_sec as f64
+		} else {
+			self.start.elapsed().as_secs_f64()
+		}
+	}
+
+	pub fn remaining(&self) -> f64 {
+		self.end - self.elapsed()
+	}
+
+	pub fn is_over(&self) -> bool {
+		self.remaining() <= 0.0
+	}
+
+	/// A budget of `seconds` from now on the same clock. It may outlast this one.
+	pub fn slice(&self, seconds: f64) -> Budget {
+		Budget { end: self.elapsed() + seconds, ..self.clone() }
+	}
+
+	/// The same budget on a clock of its own, for another thread, whose evaluations would
+	/// otherwise interleave with these nondeterministically.
+	pub fn fork(&self) -> Budget {
+		Budget { evals: Arc::new(AtomicU64::new(self.evals.load(Ordering::Relaxed))), ..self.clone() }
+	}
+
+	/// Moves the clock on by `seconds`, as when resuming a run that had already spent them.
+	pub fn skip(&mut self, seconds: f64) {
+		if self.is_counted() {
+			self.evals.fetch_add((seconds * self.evals_per_sec as f64).round() as u64, Ordering::Relaxed);
+		} else {
+			self.start -= std::time::Duration::from_secs_f64(seconds);
+		}
+	}
+}
@@ -0,0 +1,1591 @@
+//! The greedy pipeline: a target board found by tile-swap annealing, a greedy route to it that
+//! places the tiles line by line, and an annealing of the moves of that route.
+
+use rand::prelude::*;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use crate::budget::Budget;
+use crate::observer::EventKind;
+use crate::params::{Params, TargetSearch};
+use crate::schedule::Schedule;
+use crate::solver::{self, Pipeline, Polished, Polisher, Router, Tiles};
+use crate::tile_macro::{swap2, Macro, Region};
+use crate::trace::{Improvement, Step};
+use crate::{Input, Output, UnionFind};
+
+/// Share of the search time of a restart the constructive fallback is guaranteed, so that it runs
+/// even when no time is left after the search.
+const CONSTRUCT_SHARE: f64 = 0.2;
+/// Targets the constructive fallback builds to pick the cheapest estimated route from.
+const CONSTRUCT_TARGETS: usize = 8;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct Board {
+    board_size: usize,
+    board_list: Vec<u8>,
+    empty_tile_area: (usize, usize),
+}
+
+#[allow(dead_code)]
+impl Board {
+    const DCHARS: [char; 4] = ['L', 'U', 'R', 'D'];
+    const DH: [usize; 4] = [0, !0, 0, 1];
+    const DW: [usize; 4] = [!0, 0, 1, 0]; //LURD
+    fn new(board_size: usize, board_list: Vec<u8>) -> Board {
+        let mut empty_tile_area = (0, 0);
+        'mainloop: for h in 0..board_size {
+            for w in 0..board_size {
+                if board_list[h * board_size + w] == 0 {
+                    empty_tile_area = (h, w);
+                    break 'mainloop;
+                }
+            }
+        }
+        Board {
+            board_size,
+            board_list,
+            empty_tile_area,
+        }
+    }
+
+    fn from_input(input: &Input) -> Board {
+        Board::from_tiles(&input.tiles)
+    }
+
+    fn from_tiles(tiles: &Tiles) -> Board {
+        Board::new(tiles.len(), tiles.iter().flatten().map(|&tile| tile as u8).collect())
+    }
+
+    fn to_input(&self, max_iter: usize) -> Input {
+        Input {
+            n: self.board_size,
+            T: max_iter,
+            tiles: self
+                .board_list
+                .chunks(self.board_size)
+                .map(|row| row.iter().map(|&tile| tile as usize).collect())
+                .collect(),
+        }
+    }
+
+    fn from_dchar_to_didx(dchar: char) -> usize {
+        match dchar {
+            'L' => 0,
+            'U' => 1,
+            'R' => 2,
+            'D' => 3,
+            _ => unreachable!()
+        }
+    }
+
+    fn update_empty_tile_area(&mut self) {
+        'mainloop: for h in 0..self.board_size {
+            for w in 0..self.board_size {
+                if self.board_list[h * self.board_size + w] == 0 {
+                    self.empty_tile_area = (h, w);
+                    break 'mainloop;
+                }
+            }
+        }
+    }
+
+    fn move_empty_tile(&mut self, dchar: char) -> bool {
+        let didx = Board::from_dchar_to_didx(dchar);
+        let (th, tw) = (
+            self.empty_tile_area.0.wrapping_add(Self::DH[didx]),
+            self.empty_tile_area.1.wrapping_add(Self::DW[didx]),
+        );
+        if th >= self.board_size || tw >= self.board_size {
+            return false;
+        }
+        self.swap(self.empty_tile_area.0 * self.board_size + self.empty_tile_area.1 , 
+                  th * self.board_size + tw);
+        self.empty_tile_area = (th, tw);
+        true
+    }
+
+    fn swap(&mut self, idx1: usize, idx2: usize) {
+        self.board_list.swap(idx1, idx2);
+    }
+
+    fn get(&self, h: usize, w: usize) -> u8 {
+        self.board_list[h * self.board_size + w]
+    }
+
+    fn print_board(&self) {
+        for h in 0..self.board_size {
+            let mut bytes = vec![];
+            for w in 0..self.board_size {
+                bytes.push(format!("{:x}", self.board_list[h * self.board_size + w]));
+            }
+            eprintln!("{}", bytes.into_iter().collect::<String>());
+        }
+    }
+}
+
+pub type GreedyPipeline<'a> = Pipeline<TileSwapSearch<'a>, GreedyRouter, MovePolisher<'a>>;
+
+/// The pipeline, which dumps the failures of the router to stderr if `debug` is set.
+pub fn pipeline(params: &Params, debug: bool) -> GreedyPipeline<'_> {
+    Pipeline {
+        search: TileSwapSearch { params },
+        router: GreedyRouter { debug },
+        polisher: MovePolisher { params },
+    }
+}
+
+/// The target search of the pipeline, given `search_time` seconds per target.
+pub struct TileSwapSearch<'a> {
+    pub params: &'a Params,
+}
+
+impl solver::TargetSearch for TileSwapSearch<'_> {
+    fn search(&self, input: &Input, budget: &Budget, rng: &mut impl Rng) -> Option<Tiles> {
+        let search_time = f64::min(self.params.search_time(input.n) as f64, budget.remaining());
+        let target = search_target_board(&Board::from_input(input), input.T, search_time, self.params, budget, rng);
+        Some(target.to_input(input.T).tiles)
+    }
+}
+
+/// Routes with `greedy`, which needs neither time nor randomness.
+pub struct GreedyRouter {
+    /// Dumps where a route got stuck to stderr.
+    pub debug: bool,
+}
+
+impl Router for GreedyRouter {
+    fn route(&self, input: &Input, target: &Tiles, steps: &mut Vec<Step>, _budget: &Budget, _rng: &mut impl Rng) -> Output {
+        match greedy(&mut Board::from_input(input), &Board::from_tiles(target), steps) {
+            Ok(solution) => solution,
+            Err(failure) => {
+                if self.debug {
+                    failure.dump();
+                }
+                failure.moves
+            }
+        }
+    }
+}
+
+/// Anneals the moves of a route for `polish_time` seconds, from `polish_start_temp` unless told
+/// otherwise.
+pub struct MovePolisher<'a> {
+    pub params: &'a Params,
+}
+
+impl Polisher for MovePolisher<'_> {
+    fn polish(&self, input: &Input, out: Output, temp: Option<f64>, budget: &Budget, rng: &mut impl Rng) -> Polished {
+        let board = Board::from_input(input);
+        let start_temp = temp.unwrap_or(self.params.polish_start_temp);
+        let (_, out, temp, improvements) = annealing(&board, input.T, out, start_temp, self.params, budget, rng);
+        Polished { out, temp, improvements }
+    }
+}
+
+/// Finds the board the tiles should be routed to, as configured by `params.target_search`. Each search
+/// gets at most `search_time` seconds. The tile-swap search leaves `CONSTRUCT_SHARE` of them, and
+/// any it does not use, to the constructive fallback, which keeps the target of the cheapest
+/// estimated route of those it builds.
+fn search_target_board(
+    board: &Board,
+    max_iter: usize,
+    search_time: f64,
+    params: &Params,
+    budget: &Budget,
+    rng: &mut impl Rng,
+) -> Board {
+    if params.target_search == TargetSearch::SpanningTree {
+        if let Some(target) = spanning_tree_search_best_board(board, budget.slice(search_time), params, rng) {
+            return target;
+        }
+    }
+    let search_budget = budget.slice(search_time);
+    let swap_budget = search_budget.slice(search_time * (1.0 - CONSTRUCT_SHARE));
+    let mut best_board = annealing_search_best_board(board, max_iter, swap_budget, params, rng);
+    if calc_score(&best_board, 0, max_iter) <= 5e5 {
+        let targets = construct_target_boards(board, CONSTRUCT_TARGETS, &search_budget, rng);
+        if let Some(target) = targets.into_iter().min_by_key(|target| estimate_route_cost(board, target)) {
+            best_board = target;
+        }
+    }
+    best_board
+}
+
+/// Routes the tiles of `board` to their places in `best_board`. The unsolved cells form a
+/// rectangle that shrinks by a row or a column at a time, always keeping the cell the empty square
+/// ends on in `best_board`, until a 2x2 square is left around it. Every side that may go next is
+/// tried from both ends, and the line that takes the fewest moves per cell is kept. The turns that
+/// placed each cell are added to `steps`.
+fn greedy(board: &mut Board, best_board: &Board, steps: &mut Vec<Step>) -> Result<Vec<char>, RouteFailure> {
+    let board_size = board.board_size;
+    let mut solution = vec![];
+    let mut fixed = vec![false; board_size * board_size];
+    let empty_idx = best_board.board_list.iter().position(|&tile| tile == 0).unwrap();
+    let goal = (empty_idx / board_size, empty_idx % board_size);
+    let (mut top, mut bottom, mut left, mut right) = (0, board_size, 0, board_size);
+    let row = |h: usize, left: usize, right: usize| (left..right).map(|w| (h, w)).collect::<Vec<_>>();
+    let column = |w: usize, top: usize, bottom: usize| (top..bottom).map(|h| (h, w)).collect::<Vec<_>>();
+    while bottom - top > 2 || right - left > 2 {
+        // (cells, direction from the line into the rest of the rectangle, rectangle left)
+        let mut lines = vec![];
+        if bottom - top > 2 {
+            if goal.0 > top {
+                lines.push((row(top, left, right), 3, (top + 1, bottom, left, right)));
+            }
+            if goal.0 < bottom - 1 {
+                lines.push((row(bottom - 1, left, right), 1, (top, bottom - 1, left, right)));
+            }
+        }
+        if right - left > 2 {
+            if goal.1 > left {
+                lines.push((column(left, top, bottom), 2, (top, bottom, left + 1, right)));
+            }
+            if goal.1 < right - 1 {
+                lines.push((column(right - 1, top, bottom), 0, (top, bottom, left, right - 1)));
+            }
+        }
+        let mut best: Option<LineFill> = None;
+        let mut failure = None;
+        for (cells, inward, rect) in lines {
+            let reversed = cells.iter().rev().copied().collect::<Vec<_>>();
+            for cells in [cells, reversed] {
+                let (mut board_copy, mut movement, mut fixed_copy) = (board.clone(), vec![], fixed.clone());
+                let line_steps = match fill_line(&mut board_copy, best_board, &cells, inward, &mut movement, &mut fixed_copy) {
+                    Ok(line_steps) => line_steps,
+                    Err((cell, step)) => {
+                        let moves = [solution.clone(), movement].concat();
+                        failure = Some(RouteFailure { cell, step, board: board_copy, moves });
+                        continue;
+                    }
+                };
+                let cost = movement.len() as f32 / cells.len() as f32;
+                if best.as_ref().is_none_or(|best| cost < best.cost) {
+                    best = Some(LineFill { cost, board: board_copy, movement, fixed: fixed_copy, rect, steps: line_steps });
+                }
+            }
+        }
+        let Some(best) = best else {
+            return Err(failure.unwrap());
+        };
+        *board = best.board;
+        let offset = solution.len();
+        steps.extend(best.steps.into_iter().map(|Step { cell, turns }| Step {
+            cell,
+            turns: turns.start + offset..turns.end + offset,
+        }));
+        solution.extend(best.movement);
+        fixed = best.fixed;
+        (top, bottom, left, right) = best.rect;
+    }
+    let start = solution.len();
+    if let Err(cell) = finish_square(board, best_board, (top, left), goal, &mut solution) {
+        return Err(RouteFailure { cell, step: RouteStep::CornerMacro, board: board.clone(), moves: solution });
+    }
+    for cell in [(top, left), (top, left + 1), (top + 1, left + 1), (top + 1, left)] {
+        if cell != goal {
+            steps.push(Step { cell, turns: start..solution.len() });
+        }
+    }
+    Ok(solution)
+}
+
+/// A line `greedy` may fill next, as filled on copies of the board and the fixed cells.
+struct LineFill {
+    /// Moves per cell.
+    cost: f32,
+    board: Board,
+    movement: Vec<char>,
+    fixed: Vec<bool>,
+    /// The rectangle of unsolved cells left, as (top, bottom, left, right).
+    rect: (usize, usize, usize, usize),
+    steps: Vec<Step>,
+}
+
+/// Fixes the `cells` of a line in turn. The last two are placed together: the tile of the last
+/// cell is parked on the one before it, that cell's tile next to it towards `inward`, and both
+/// slide into place with two moves of the empty square. Returns the turns of `solution` that
+/// placed each cell.
+fn fill_line(
+    board: &mut Board,
+    best_board: &Board,
+    cells: &[(usize, usize)],
+    inward: usize,
+    solution: &mut Vec<char>,
+    fixed: &mut Vec<bool>,
+) -> Result<Vec<Step>, ((usize, usize), RouteStep)> {
+    let board_size = board.board_size;
+    let len = cells.len();
+    let mut steps = vec![];
+    for &(h, w) in &cells[..len - 2] {
+        let start = solution.len();
+        move_tile(board, best_board, (h, w), (h, w), solution, fixed).map_err(|step| ((h, w), step))?;
+        if articulation_points(board_size, fixed)[h * board_size + w] {
+            return Err(((h, w), RouteStep::SplitRegion));
+        }
+        fixed[h * board_size + w] = true;
+        steps.push(Step { cell: (h, w), turns: start..solution.len() });
+    }
+    let (last, second) = (cells[len - 1], cells[len - 2]);
+    // the move of the empty square from `last` to `second`
+    let backward = match (last.0.cmp(&second.0), last.1.cmp(&second.1)) {
+        (_, Ordering::Greater) => 0,
+        (Ordering::Greater, _) => 1,
+        (_, Ordering::Less) => 2,
+        _ => 3,
+    };
+    let parking = (
+        second.0.wrapping_add(Board::DH[inward]),
+        second.1.wrapping_add(Board::DW[inward]),
+    );
+    let step = |(h, w): (usize, usize)| (h.wrapping_add(Board::DH[inward]), w.wrapping_add(Board::DW[inward]));
+    let (board_backup, solution_len, fixed_backup) = (board.clone(), solution.len(), fixed.clone());
+    // The empty square can get shut in the corner at `last` beside the tile meant for `second`.
+    // Moving that tile off the line first, and holding it there while the tile of `last` is
+    // placed if the region is wide enough, avoids it.
+    let mut aways = vec![];
+    let mut away = parking;
+    for _ in 0..3 {
+        away = step(away);
+        aways.extend([Some((away, true)), Some((away, false))]);
+    }
+    for away in std::iter::once(None).chain(aways) {
+        *board = board_backup.clone();
+        solution.truncate(solution_len);
+        fixed.clone_from(&fixed_backup);
+        if let Some(((h, w), hold)) = away {
+            if h >= board_size || w >= board_size || fixed[h * board_size + w] {
+                continue;
+            }
+            if move_tile(board, best_board, second, (h, w), solution, fixed).is_err() {
+                continue;
+            }
+            fixed[h * board_size + w] = hold;
+        }
+        let placed = move_tile(board, best_board, last, second, solution, fixed);
+        if let Some(((h, w), _)) = away {
+            fixed[h * board_size + w] = false;
+        }
+        if placed.is_err() {
+            continue;
+        }
+        fixed[second.0 * board_size + second.1] = true;
+        if move_tile(board, best_board, second, parking, solution, fixed).is_err() {
+            continue;
+        }
+        fixed[parking.0 * board_size + parking.1] = true;
+        if !move_empty_tile_to_goal(board, last, solution, fixed) {
+            continue;
+        }
+        for dchar in [Board::DCHARS[backward], Board::DCHARS[inward]] {
+            board.move_empty_tile(dchar);
+            solution.push(dchar);
+        }
+        fixed[last.0 * board_size + last.1] = true;
+        fixed[parking.0 * board_size + parking.1] = false;
+        for cell in [second, last] {
+            steps.push(Step { cell, turns: solution_len..solution.len() });
+        }
+        return Ok(steps);
+    }
+    Err((last, RouteStep::CornerMacro))
+}
+
+/// Moves the empty square around the 2x2 square at `corner` until it reaches `goal`, picking the
+/// rotation of the three tiles left that matches the most cells of `best_board`. Returns a cell
+/// still wrong if the tiles cannot be matched.
+fn finish_square(
+    board: &mut Board,
+    best_board: &Board,
+    corner: (usize, usize),
+    goal: (usize, usize),
+    solution: &mut Vec<char>,
+) -> Result<(), (usize, usize)> {
+    let (h, w) = corner;
+    let cells = [(h, w), (h, w + 1), (h + 1, w + 1), (h + 1, w)];
+    // moves from each cell to the next one, clockwise and counterclockwise
+    let rotations = [['R', 'D', 'L', 'U'], ['D', 'L', 'U', 'R']];
+    let mut best: Option<(usize, Vec<char>)> = None;
+    for dchars in rotations {
+        let mut board_copy = board.clone();
+        let mut movement = vec![];
+        // Three laps bring the tiles back to where they started.
+        for _ in 0..12 {
+            if board_copy.empty_tile_area == goal {
+                let matches = cells.iter().filter(|&&(h, w)| board_copy.get(h, w) == best_board.get(h, w)).count();
+                if best.as_ref().is_none_or(|(best_matches, best_movement)| {
+                    matches > *best_matches || (matches == *best_matches && movement.len() < best_movement.len())
+                }) {
+                    best = Some((matches, movement.clone()));
+                }
+            }
+            let Some(pos) = cells.iter().position(|&cell| cell == board_copy.empty_tile_area) else {
+                return Err(goal);
+            };
+            board_copy.move_empty_tile(dchars[pos]);
+            movement.push(dchars[pos]);
+        }
+    }
+    if let Some((_, movement)) = best {
+        for dchar in movement {
+            board.move_empty_tile(dchar);
+            solution.push(dchar);
+        }
+    }
+    let wrong = cells.iter().filter(|&&(h, w)| board.get(h, w) != best_board.get(h, w)).copied().collect::<Vec<_>>();
+    if let [a, b] = wrong[..] {
+        // The tiles left are an odd permutation away from the target. Swapping them together with
+        // two tiles of the same type elsewhere makes it even.
+        if let Some(swap) = twin_swap(board, a, b) {
+            for dchar in swap.moves {
+                board.move_empty_tile(dchar);
+                solution.push(dchar);
+            }
+        }
+    }
+    match cells.iter().find(|&&(h, w)| board.get(h, w) != best_board.get(h, w)) {
+        Some(&cell) => Err(cell),
+        None => Ok(()),
+    }
+}
+
+/// Cheapest macro that swaps the tiles at `a` and `b` along with two equal neighbouring tiles,
+/// trying the `MAX_TWINS` pairs closest to `a`.
+fn twin_swap(board: &Board, a: (usize, usize), b: (usize, usize)) -> Option<Macro> {
+    const MAX_TWINS: usize = 4;
+    let board_size = board.board_size;
+    let mut twins = vec![];
+    for h in 0..board_size {
+        for w in 0..board_size {
+            for (h_to, w_to) in [(h, w + 1), (h + 1, w)] {
+                if h_to >= board_size || w_to >= board_size || board.get(h, w) == 0 || board.get(h, w) != board.get(h_to, w_to) {
+                    continue;
+                }
+                if [a, b].iter().any(|&p| p == (h, w) || p == (h_to, w_to)) {
+                    continue;
+                }
+                twins.push(((h, w), (h_to, w_to)));
+            }
+        }
+    }
+    twins.sort_by_key(|&((h, w), _)| h.abs_diff(a.0) + w.abs_diff(a.1));
+    twins
+        .into_iter()
+        .take(MAX_TWINS)
+        .filter_map(|twin| swap2(&Region::whole(board_size), board.empty_tile_area, [(a, b), twin]))
+        .min_by_key(|swap| swap.cost())
+}
+
+/// The step of the routing that failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RouteStep {
+    /// No tile of the type needed is reachable through the free cells.
+    TileSearch,
+    /// None of the tiles has a path to the goal through the free cells.
+    TilePath,
+    /// The tiles have paths, but the empty square could not get ahead of the tile along any.
+    HolePath,
+    /// The two cells at the end of a line, or the last 2x2 square, could not be filled.
+    CornerMacro,
+    /// Fixing the cell would split the free cells in two.
+    SplitRegion,
+}
+
+/// Where `greedy` had to stop.
+#[derive(Clone, Debug)]
+struct RouteFailure {
+    /// The cell being filled.
+    cell: (usize, usize),
+    step: RouteStep,
+    /// The board when the step failed.
+    board: Board,
+    /// The moves that led to `board`.
+    moves: Vec<char>,
+}
+
+impl RouteFailure {
+    fn dump(&self) {
+        eprintln!(
+            "greedy failed at {:?} in {:?} after {} moves",
+            self.cell,
+            self.step,
+            self.moves.len()
+        );
+        self.board.print_board();
+    }
+}
+
+/// Brings a tile of the type `best_board` has at `best_tile_pos` to `goal`. While a tile moves, the
+/// empty square walks around it, which fails if the tile sits on a cut vertex of the free cells and
+/// the empty square is on the wrong side. Paths avoiding such cells are tried first.
+fn move_tile(
+    board: &mut Board,
+    best_board: &Board,
+    best_tile_pos: (usize, usize),
+    goal: (usize, usize),
+    solution: &mut Vec<char>,
+    fixed: &mut [bool],
+) -> Result<(), RouteStep> {
+    let board_size = board.board_size;
+    let best_tile = best_board.get(best_tile_pos.0, best_tile_pos.1);
+    if board.get(goal.0, goal.1) == best_tile {
+        return Ok(());
+    }
+    let tile_positions = search_tiles(board, best_tile, goal, fixed).ok_or(RouteStep::TileSearch)?;
+    let cut = articulation_points(board_size, fixed);
+    let mut error = RouteStep::TilePath;
+    for start in tile_positions {
+        let mut blocked = fixed.iter().zip(&cut).map(|(&fixed, &cut)| fixed || cut).collect::<Vec<_>>();
+        blocked[start.0 * board_size + start.1] = false;
+        blocked[goal.0 * board_size + goal.1] = false;
+        let safe_path = find_path(board, start, goal, &blocked);
+        let path = find_path(board, start, goal, fixed);
+        let paths = if safe_path == path { vec![path] } else { vec![safe_path, path] };
+        'pathloop: for tile_path in paths.into_iter().flatten() {
+            let mut tile_pos = start;
+            let mut board_copy = board.clone();
+            let mut movement = vec![];
+            for dchar in tile_path {
+                let didx = Board::from_dchar_to_didx(dchar);
+                fixed[tile_pos.0 * board_size + tile_pos.1] = true;
+                let nxt_tile_pos = (
+                    tile_pos.0.wrapping_add(Board::DH[didx]),
+                    tile_pos.1.wrapping_add(Board::DW[didx]),
+                );
+                if !move_empty_tile_to_goal(&mut board_copy, nxt_tile_pos, &mut movement, fixed) {
+                    fixed[tile_pos.0 * board_size + tile_pos.1] = false;
+                    error = RouteStep::HolePath;
+                    continue 'pathloop;
+                }
+                movement.push(Board::DCHARS[(didx + 2) % 4]);
+                board_copy.move_empty_tile(Board::DCHARS[(didx + 2) % 4]);
+                fixed[tile_pos.0 * board_size + tile_pos.1] = false;
+                tile_pos = nxt_tile_pos;
+            }
+            for dchar in movement {
+                solution.push(dchar);
+                board.move_empty_tile(dchar);
+            }
+            return Ok(());
+        }
+    }
+    Err(error)
+}
+
+/// Cells whose removal disconnects the cells not in `fixed`.
+fn articulation_points(board_size: usize, fixed: &[bool]) -> Vec<bool> {
+    let mut finder = CutFinder {
+        board_size,
+        fixed,
+        order: vec![0; board_size * board_size],
+        low: vec![0; board_size * board_size],
+        cut: vec![false; board_size * board_size],
+        counter: 0,
+    };
+    for (idx, &is_fixed) in fixed.iter().enumerate() {
+        if !is_fixed && finder.order[idx] == 0 {
+            finder.dfs(idx, !0);
+        }
+    }
+    finder.cut
+}
+
+/// Depth-first search state of `articulation_points` (Tarjan's lowlink).
+struct CutFinder<'a> {
+    board_size: usize,
+    fixed: &'a [bool],
+    order: Vec<usize>,
+    low: Vec<usize>,
+    cut: Vec<bool>,
+    counter: usize,
+}
+
+impl CutFinder<'_> {
+    fn dfs(&mut self, idx: usize, parent: usize) {
+        let board_size = self.board_size;
+        self.counter += 1;
+        self.order[idx] = self.counter;
+        self.low[idx] = self.counter;
+        let mut children = 0;
+        for didx in 0..4 {
+            let (h_to, w_to) = (
+                (idx / board_size).wrapping_add(Board::DH[didx]),
+                (idx % board_size).wrapping_add(Board::DW[didx]),
+            );
+            if h_to >= board_size || w_to >= board_size || self.fixed[h_to * board_size + w_to] {
+                continue;
+            }
+            let idx_to = h_to * board_size + w_to;
+            if self.order[idx_to] == 0 {
+                children += 1;
+                self.dfs(idx_to, idx);
+                self.low[idx] = usize::min(self.low[idx], self.low[idx_to]);
+                if parent != !0 && self.low[idx_to] >= self.order[idx] {
+                    self.cut[idx] = true;
+                }
+            } else if idx_to != parent {
+                self.low[idx] = usize::min(self.low[idx], self.order[idx_to]);
+            }
+        }
+        if parent == !0 && children > 1 {
+            self.cut[idx] = true;
+        }
+    }
+}
+
+fn move_empty_tile_to_goal(
+    board: &mut Board,
+    goal: (usize, usize),
+    solution: &mut Vec<char>,
+    fixed: &[bool],
+) -> bool {
+    if goal == board.empty_tile_area {
+        return true;
+    }
+    if let Some(empty_tile_path) = find_path(board, board.empty_tile_area, goal, fixed) {
+        for dchar in empty_tile_path {
+            solution.push(dchar);
+            board.move_empty_tile(dchar);
+        }
+        return true;
+    }
+    false
+    
+}
+
+fn search_tiles(board: &Board, tile: u8, start: (usize, usize), fixed: &[bool]) -> Option<Vec<(usize, usize)>> {
+    let board_size = board.board_size;
+    let mut que = VecDeque::new();
+    let mut seen = vec![false; board_size * board_size];
+    que.push_back(start);
+    seen[start.0 * board_size + start.1] = true;
+    let mut positions = vec![];
+    while let Some((h_now, w_now)) = que.pop_front() {
+        if board.get(h_now, w_now) == tile {
+            positions.push((h_now, w_now));
+        }
+        for didx in 0..4 {
+            let (h_to, w_to) = (
+                h_now.wrapping_add(Board::DH[didx]),
+                w_now.wrapping_add(Board::DW[didx]),
+            );
+            if h_to >= board_size || w_to >= board_size {
+                continue;
+            }
+            if !fixed[h_to * board_size + w_to] && !seen[h_to * board_size + w_to] {
+                seen[h_to * board_size + w_to] = true;
+                que.push_back((h_to, w_to));
+            }
+        }
+    }
+    if !positions.is_empty() {
+        return Some(positions);
+    }
+    None
+}
+
+fn find_path(
+    board: &Board,
+    start: (usize, usize),
+    goal: (usize, usize),
+    fixed: &[bool],
+) -> Option<Vec<char>> {
+    let board_size = board.board_size;
+    let mut que = VecDeque::new();
+    let mut dist = vec![i32::MAX; board_size * board_size];
+    let mut prev = vec![None; board_size * board_size];
+    que.push_back(start);
+    dist[start.0 * board_size + start.1] = 0;
+    while let Some((h_now, w_now)) = que.pop_front() {
+        if (h_now, w_now) == goal {
+            break;
+        }
+        for didx in 0..4 {
+            let (h_to, w_to) = (
+                h_now.wrapping_add(Board::DH[didx]),
+                w_now.wrapping_add(Board::DW[didx]),
+            );
+            if h_to >= board_size || w_to >= board_size {
+                continue;
+            }
+            if fixed[h_to * board_size + w_to] {
+                continue;
+            }
+            if dist[h_to * board_size + w_to] > dist[h_now * board_size + w_now] + 1 {
+                dist[h_to * board_size + w_to] = dist[h_now * board_size + w_now] + 1;
+                prev[h_to * board_size + w_to] = Some(((h_now, w_now), Board::DCHARS[didx]));
+                que.push_back((h_to, w_to));
+            }
+        }
+    }
+    prev[goal.0 * board_size + goal.1]?;
+    let mut path = vec![];
+    let mut now_pos = goal;
+    while let Some((nxt_pos, dchar)) = prev[now_pos.0 * board_size + now_pos.1] {
+        now_pos = nxt_pos;
+        path.push(dchar);
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// Anneals swaps of tiles, the empty square included, towards a larger tree, more matched edges
+/// and less tile travel. Returns the full tree of the cheapest estimated route it came across, or
+/// else the board with the largest tree.
+fn annealing_search_best_board(
+    init_board: &Board, 
+    max_iter: usize, 
+    budget: Budget, 
+    params: &Params,
+    rng: &mut impl Rng
+) -> Board {
+    // Tiles of the tree a matched edge is worth.
+    const EDGE_WEIGHT: f32 = 0.05;
+    let board_size = init_board.board_size;
+    let mut board = init_board.clone();
+    // origin[idx]: where the tile at idx was initially. The empty square takes part in the swaps
+    // too, so the target may leave it anywhere.
+    let mut origin = (0..board_size * board_size).collect::<Vec<_>>();
+    let cells = board_size * board_size;
+    // Looked up rather than computed, as the divisions would cost as much as the rest of a swap.
+    let distances = (0..cells * cells).map(|k| {
+        let (from, to) = (k / cells, k % cells);
+        ((from / board_size).abs_diff(to / board_size) + (from % board_size).abs_diff(to % board_size)) as i32
+    }).collect::<Vec<_>>();
+    let distance = |from: usize, to: usize| distances[from * cells + to];
+    let tile_num = (board_size * board_size - 1) as i32;
+    let weight = params.route_weight * 5e5 / max_iter as f32;
+    // As in `calc_score`, a full tree is worth twice what a tree of all but one of the tiles is.
+    let tree_score = |size: i32| if size == tile_num { 1e6 } else { 5e5 * size as f32 / tile_num as f32 };
+    // Most swaps leave the size of the tree as it is. Matched edges tell those apart, so that
+    // the search is not blind while the tree cannot grow.
+    let edge_score = EDGE_WEIGHT * 5e5 / tile_num as f32;
+    let mut components = Components::new(&board);
+    let mut tree_size = components.largest_size;
+    let mut route_cost: i32 = (0..board_size * board_size).map(|idx| distance(origin[idx], idx)).sum();
+    let mut best_board = board.clone();
+    let mut best_tree_size = tree_size;
+    let mut best_full_tree: Option<(i32, Board)> = None;
+    let config = params.schedule(params.search_start_temp, params.search_end_temp);
+    let mut schedule = Schedule::new(config, budget);
+    while schedule.advance() {
+        let choice1 = rng.gen_range(0, board_size * board_size - 1);
+        let choice2 = rng.gen_range(choice1 + 1, board_size * board_size);
+        let route_delta = distance(origin[choice2], choice1) + distance(origin[choice1], choice2)
+            - distance(origin[choice1], choice1)
+            - distance(origin[choice2], choice2);
+        let route_score = -weight * route_delta as f32;
+        let (edge_delta, new_tree_size) = components.propose_swap(&mut board, choice1, choice2);
+        let delta = tree_score(new_tree_size) - tree_score(tree_size) + edge_score * edge_delta as f32 + route_score;
+        if !schedule.accept(delta as f64, rng) {
+            board.swap(choice1, choice2);
+            continue;
+        }
+        components.accept(&board);
+        tree_size = new_tree_size;
+        route_cost += route_delta;
+        origin.swap(choice1, choice2);
+        if tree_size > best_tree_size {
+            best_tree_size = tree_size;
+            best_board = board.clone();
+            schedule.budget().report(EventKind::Best, "target search", schedule.iter(), best_tree_size as f64, 0);
+        }
+        if tree_size == tile_num && is_reachable(init_board, &board) {
+            let estimate = estimate_route_cost(init_board, &board);
+            if best_full_tree.as_ref().is_none_or(|(cost, _)| *cost > estimate) {
+                best_full_tree = Some((estimate, board.clone()));
+            }
+        }
+    }
+    if let Some((_, board)) = best_full_tree.as_ref() {
+        best_board = board.clone();
+    }
+    let best_score = calc_score(&best_board, 0, max_iter);
+    eprintln!(
+        "search iter num = {}, full searches = {}, best score = {}, route cost = {}, best tree estimate = {:?}",
+        schedule.iter(),
+        components.full_searches,
+        best_score,
+        route_cost,
+        best_full_tree.map(|(cost, _)| cost)
+    );
+    schedule.report("search");
+    schedule.budget().report(EventKind::Done, "target search", schedule.iter(), best_score as f64, 0);
+    best_board.update_empty_tile_area();
+    best_board
+}
+
+/// Estimated number of tile moves needed to turn `init_board` into `target`: the sum over the
+/// target cells, in row-major order, of the distance to the nearest matching tile
+/// not yet assigned to an earlier cell. The empty square counts as a tile.
+fn estimate_route_cost(init_board: &Board, target: &Board) -> i32 {
+    let board_size = init_board.board_size;
+    let mut assigned = vec![false; board_size * board_size];
+    let mut cost = 0;
+    for h in 0..board_size {
+        for w in 0..board_size {
+            let tile = target.get(h, w);
+            let nearest = (0..board_size * board_size)
+                .filter(|&idx| !assigned[idx] && init_board.board_list[idx] == tile)
+                .min_by_key(|&idx| (idx / board_size).abs_diff(h) + (idx % board_size).abs_diff(w));
+            if let Some(idx) = nearest {
+                assigned[idx] = true;
+                cost += ((idx / board_size).abs_diff(h) + (idx % board_size).abs_diff(w)) as i32;
+            }
+        }
+    }
+    cost
+}
+
+/// Whether sliding moves can turn `init_board` into `target`. Two tiles of the same type can trade
+/// places, so only boards whose tiles are all distinct are bound by the parity of the permutation.
+fn is_reachable(init_board: &Board, target: &Board) -> bool {
+    let board_size = init_board.board_size;
+    let mut position = [!0; 16];
+    for (idx, &tile) in target.board_list.iter().enumerate() {
+        if position[tile as usize] != !0 {
+            return true;
+        }
+        position[tile as usize] = idx;
+    }
+    let perm = init_board.board_list.iter().map(|&tile| position[tile as usize]).collect::<Vec<_>>();
+    let mut seen = vec![false; perm.len()];
+    let mut transpositions = 0;
+    for start in 0..perm.len() {
+        if seen[start] {
+            continue;
+        }
+        let mut idx = start;
+        while !seen[idx] {
+            seen[idx] = true;
+            idx = perm[idx];
+            transpositions += 1;
+        }
+        transpositions -= 1;
+    }
+    let (from, to) = (
+        init_board.board_list.iter().position(|&tile| tile == 0).unwrap(),
+        position[0],
+    );
+    let empty_distance = (from / board_size).abs_diff(to / board_size) + (from % board_size).abs_diff(to % board_size);
+    (transpositions + empty_distance).is_multiple_of(2)
+}
+
+/// A cell for the empty square of a target: one of the `spread` cells nearest to where it starts,
+/// as every cell it travels costs a move.
+fn pick_hole(init_board: &Board, spread: usize, rng: &mut impl Rng) -> usize {
+    let board_size = init_board.board_size;
+    let (h, w) = init_board.empty_tile_area;
+    let mut cells = (0..board_size * board_size).collect::<Vec<_>>();
+    cells.shuffle(rng);
+    cells.sort_by_key(|&idx| (idx / board_size).abs_diff(h) + (idx % board_size).abs_diff(w));
+    cells[rng.gen_range(0, spread.clamp(1, cells.len()))]
+}
+
+/// Walks over spanning trees of the cells other than the one `pick_hole` leaves empty by edge
+/// flips: a random edge is added to the tree and a random edge of the cycle it closes is removed.
+/// The tiles a tree implies are scored by how far their multiset is from the tiles of
+/// `init_board`, and by the distance from every cell, the empty one included, to the nearest
+/// initial tile of the type it needs. Returns the cheapest tree whose tiles match exactly and that
+/// is reachable from `init_board`, if one was found within `budget`.
+fn spanning_tree_search_best_board(
+    init_board: &Board,
+    budget: Budget,
+    params: &Params,
+    rng: &mut impl Rng,
+) -> Option<Board> {
+    const MISMATCH_WEIGHT: i32 = 20;
+    let board_size = init_board.board_size;
+    let cell_num = board_size * board_size;
+    let hole = pick_hole(init_board, board_size, rng);
+    let mut target_counts = [0; 16];
+    for &tile in &init_board.board_list {
+        target_counts[tile as usize] += 1;
+    }
+    target_counts[0] = 0;
+    // dist[tile][idx]: distance from idx to the nearest initial tile of that type
+    let far = 2 * board_size as i32;
+    let mut dist = vec![vec![far; cell_num]; 16];
+    for (from, &tile) in init_board.board_list.iter().enumerate() {
+        for (idx, dist) in dist[tile as usize].iter_mut().enumerate() {
+            let d = ((idx / board_size).abs_diff(from / board_size)
+                + (idx % board_size).abs_diff(from % board_size)) as i32;
+            *dist = i32::min(*dist, d);
+        }
+    }
+    let mut edges = vec![];
+    for idx in (0..cell_num).filter(|&idx| idx != hole) {
+        for didx in [2, 3] {
+            let (h_to, w_to) = (
+                (idx / board_size).wrapping_add(Board::DH[didx]),
+                (idx % board_size).wrapping_add(Board::DW[didx]),
+            );
+            if h_to < board_size && w_to < board_size && h_to * board_size + w_to != hole {
+                edges.push((idx, didx));
+            }
+        }
+    }
+    edges.shuffle(rng);
+    let mut uf = UnionFind::new(cell_num);
+    let mut tiles = vec![0u8; cell_num];
+    for &(idx, didx) in &edges {
+        let idx_to = neighbor(board_size, idx, didx);
+        if !uf.same(idx, idx_to) {
+            uf.unite(idx, idx_to);
+            tiles[idx] |= 1 << didx;
+            tiles[idx_to] |= 1 << ((didx + 2) % 4);
+        }
+    }
+    let mut counts = [0; 16];
+    for &tile in &tiles {
+        counts[tile as usize] += 1;
+    }
+    let mismatch = |counts: &[i32; 16]| -> i32 {
+        (1..16).map(|tile| (counts[tile] - target_counts[tile]).abs()).sum()
+    };
+    // The empty cell is the only one of type 0, so its travel is in here too.
+    let mut route_cost: i32 = (0..cell_num).map(|idx| dist[tiles[idx] as usize][idx]).sum();
+    let mut cost = MISMATCH_WEIGHT * mismatch(&counts) + route_cost;
+    let mut best: Option<(i32, Vec<u8>)> = None;
+    let config = params.schedule(params.tree_start_temp, params.tree_end_temp);
+    let mut schedule = Schedule::new(config, budget);
+    while schedule.advance() {
+        let &(idx1, didx1) = edges.choose(rng).unwrap();
+        if tiles[idx1] >> didx1 & 1 == 1 {
+            continue;
+        }
+        let idx2 = neighbor(board_size, idx1, didx1);
+        let cycle = tree_path(board_size, &tiles, idx2, idx1);
+        let (idx3, didx3) = *cycle.choose(rng).unwrap();
+        let idx4 = neighbor(board_size, idx3, didx3);
+        let mut changed = vec![idx1, idx2, idx3, idx4];
+        changed.sort();
+        changed.dedup();
+        let mut new_counts = counts;
+        let mut new_route_cost = route_cost;
+        for &idx in &changed {
+            new_counts[tiles[idx] as usize] -= 1;
+            new_route_cost -= dist[tiles[idx] as usize][idx];
+        }
+        tiles[idx1] ^= 1 << didx1;
+        tiles[idx2] ^= 1 << ((didx1 + 2) % 4);
+        tiles[idx3] ^= 1 << didx3;
+        tiles[idx4] ^= 1 << ((didx3 + 2) % 4);
+        for &idx in &changed {
+            new_counts[tiles[idx] as usize] += 1;
+            new_route_cost += dist[tiles[idx] as usize][idx];
+        }
+        let new_mismatch = mismatch(&new_counts);
+        let new_cost = MISMATCH_WEIGHT * new_mismatch + new_route_cost;
+        if schedule.accept((cost - new_cost) as f64, rng) {
+            counts = new_counts;
+            route_cost = new_route_cost;
+            cost = new_cost;
+            if new_mismatch == 0
+                && best.as_ref().is_none_or(|(c, _)| *c > route_cost)
+                && is_reachable(init_board, &Board::new(board_size, tiles.clone()))
+            {
+                best = Some((route_cost, tiles.clone()));
+            }
+        } else {
+            tiles[idx1] ^= 1 << didx1;
+            tiles[idx2] ^= 1 << ((didx1 + 2) % 4);
+            tiles[idx3] ^= 1 << didx3;
+            tiles[idx4] ^= 1 << ((didx3 + 2) % 4);
+        }
+    }
+    eprintln!(
+        "spanning tree iter num = {}, best route cost = {:?}",
+        schedule.iter(),
+        best.as_ref().map(|(c, _)| *c)
+    );
+    schedule.report("spanning tree");
+    let best = best.map(|(_, tiles)| Board::new(board_size, tiles));
+    let best_score = best.as_ref().map_or(0.0, |board| calc_score(board, 0, 1));
+    schedule.budget().report(EventKind::Done, "spanning tree", schedule.iter(), best_score as f64, 0);
+    best
+}
+
+fn neighbor(board_size: usize, idx: usize, didx: usize) -> usize {
+    (idx / board_size).wrapping_add(Board::DH[didx]) * board_size
+        + (idx % board_size).wrapping_add(Board::DW[didx])
+}
+
+/// Edges, as (cell, direction), on the path from `start` to `goal` in the tree given by `tiles`.
+fn tree_path(board_size: usize, tiles: &[u8], start: usize, goal: usize) -> Vec<(usize, usize)> {
+    let mut prev = vec![None; tiles.len()];
+    let mut que = VecDeque::new();
+    prev[start] = Some((start, 4));
+    que.push_back(start);
+    while let Some(idx) = que.pop_front() {
+        if idx == goal {
+            break;
+        }
+        for didx in 0..4 {
+            if tiles[idx] >> didx & 1 == 1 {
+                let idx_to = neighbor(board_size, idx, didx);
+                if prev[idx_to].is_none() {
+                    prev[idx_to] = Some((idx, didx));
+                    que.push_back(idx_to);
+                }
+            }
+        }
+    }
+    let mut path = vec![];
+    let mut idx = goal;
+    while idx != start {
+        let (idx_from, didx) = prev[idx].unwrap();
+        path.push((idx_from, didx));
+        idx = idx_from;
+    }
+    path
+}
+
+/// Builds target boards whose tiles are exactly the tiles of `init_board` and form a spanning
+/// tree. Every restart leaves a cell near the initial empty square empty, farther afield the more
+/// restarts failed, as some cells admit no tree at all. Tiles are placed in row-major order by a
+/// depth-first search that restarts with a new random candidate order after `NODE_LIMIT` nodes.
+/// Returns up to `count` distinct boards reachable from `init_board` found before `budget` is
+/// over.
+fn construct_target_boards(
+    init_board: &Board,
+    count: usize,
+    budget: &Budget,
+    rng: &mut impl Rng,
+) -> Vec<Board> {
+    const NODE_LIMIT: usize = 20000;
+    let board_size = init_board.board_size;
+    let mut counts = [0; 16];
+    for &tile in &init_board.board_list {
+        counts[tile as usize] += 1;
+    }
+    counts[0] = 0;
+    let mut found = std::collections::HashSet::new();
+    let mut boards = vec![];
+    let mut restarts = 0;
+    while boards.len() < count && !budget.is_over() {
+        restarts += 1;
+        let hole = pick_hole(init_board, board_size + restarts, rng);
+        let mut builder = TreeBuilder::new(board_size, hole, counts, NODE_LIMIT);
+        let built = builder.dfs(0, rng);
+        budget.tick_n(builder.nodes as u64);
+        if !built || !found.insert(builder.tiles.clone()) {
+            continue;
+        }
+        // With all tiles distinct, half of the trees are of the wrong parity.
+        let board = Board::new(board_size, builder.tiles);
+        if is_reachable(init_board, &board) {
+            boards.push(board);
+        }
+    }
+    eprintln!("construct restarts = {}, targets = {}", restarts, boards.len());
+    let best_score = boards.iter().map(|board| calc_score(board, 0, 1)).fold(0.0, f32::max);
+    budget.report(EventKind::Done, "construct", restarts, best_score as f64, 0);
+    boards
+}
+
+/// Depth-first search state of `construct_target_boards`. Connectivity is tracked with a
+/// union-find without path compression so that each placement can be undone.
+struct TreeBuilder {
+    board_size: usize,
+    /// The cell left empty.
+    hole: usize,
+    counts: [usize; 16],
+    tiles: Vec<u8>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    /// Lines of a component that point at cells not placed yet, kept at its root.
+    open: Vec<usize>,
+    history: Vec<(usize, usize, usize)>,
+    nodes: usize,
+    node_limit: usize,
+}
+
+impl TreeBuilder {
+    fn new(board_size: usize, hole: usize, counts: [usize; 16], node_limit: usize) -> TreeBuilder {
+        let cell_num = board_size * board_size;
+        TreeBuilder {
+            board_size,
+            hole,
+            counts,
+            tiles: vec![0; cell_num],
+            parent: (0..cell_num).collect(),
+            size: vec![1; cell_num],
+            open: vec![0; cell_num],
+            history: vec![],
+            nodes: 0,
+            node_limit,
+        }
+    }
+
+    fn find(&self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            idx = self.parent[idx];
+        }
+        idx
+    }
+
+    /// Unites the components of `idx1` and `idx2`, one of whose open lines met the other.
+    fn unite(&mut self, idx1: usize, idx2: usize) {
+        let (mut root1, mut root2) = (self.find(idx1), self.find(idx2));
+        if self.size[root1] < self.size[root2] {
+            std::mem::swap(&mut root1, &mut root2);
+        }
+        self.history.push((root2, self.size[root1], self.open[root1]));
+        self.parent[root2] = root1;
+        self.size[root1] += self.size[root2];
+        self.open[root1] = self.open[root1] + self.open[root2] - 1;
+    }
+
+    fn rollback(&mut self, len: usize) {
+        while self.history.len() > len {
+            let (root2, size, open) = self.history.pop().unwrap();
+            let root1 = self.parent[root2];
+            self.parent[root2] = root2;
+            self.size[root1] = size;
+            self.open[root1] = open;
+        }
+    }
+
+    /// Whether the tiles left can still fill the cells left, judged by their line counts.
+    fn is_feasible(&self, idx: usize) -> bool {
+        let (board_size, hole) = (self.board_size, self.hole);
+        let cell_num = board_size * board_size;
+        let (mut need_up, mut need_no_up, mut need_left) = (0, 0, 0);
+        for next in (idx + 1..usize::min(idx + 1 + board_size, cell_num)).filter(|&next| next != hole) {
+            if next >= board_size && self.tiles[next - board_size] & 8 != 0 {
+                need_up += 1;
+            } else {
+                need_no_up += 1;
+            }
+        }
+        if !(idx + 1).is_multiple_of(board_size) && self.tiles[idx] & 4 != 0 {
+            need_left += 1;
+        }
+        let (mut up, mut no_up, mut left, mut right, mut down) = (0, 0, 0, 0, 0);
+        for tile in 1..16 {
+            let c = self.counts[tile];
+            if tile & 2 != 0 {
+                up += c;
+            } else {
+                no_up += c;
+            }
+            if tile & 1 != 0 {
+                left += c;
+            }
+            if tile & 4 != 0 {
+                right += c;
+            }
+            if tile & 8 != 0 {
+                down += c;
+            }
+        }
+        // cells after idx that may hold a line to the right or downwards
+        let (mut right_cells, mut down_cells) = (0, 0);
+        for next in (idx + 1..cell_num).filter(|&next| next != hole) {
+            let (h, w) = (next / board_size, next % board_size);
+            if w + 1 < board_size && next + 1 != hole {
+                right_cells += 1;
+            }
+            if h + 1 < board_size && next + board_size != hole {
+                down_cells += 1;
+            }
+        }
+        up >= need_up
+            && no_up >= need_no_up
+            && left >= need_left
+            && right <= right_cells
+            && down <= down_cells
+    }
+
+    fn dfs(&mut self, idx: usize, rng: &mut impl Rng) -> bool {
+        let (board_size, hole) = (self.board_size, self.hole);
+        let cell_num = board_size * board_size;
+        if idx == cell_num {
+            return true;
+        }
+        if idx == hole {
+            return self.dfs(idx + 1, rng);
+        }
+        self.nodes += 1;
+        if self.nodes > self.node_limit {
+            return false;
+        }
+        let (h, w) = (idx / board_size, idx % board_size);
+        let need_left = w > 0 && self.tiles[idx - 1] & 4 != 0;
+        let need_up = h > 0 && self.tiles[idx - board_size] & 8 != 0;
+        if need_left && need_up && self.find(idx - 1) == self.find(idx - board_size) {
+            return false;
+        }
+        let can_right = w + 1 < board_size && idx + 1 != hole;
+        let can_down = h + 1 < board_size && idx + board_size != hole;
+        let mut candidates = (1..16u8)
+            .filter(|&tile| {
+                self.counts[tile as usize] > 0
+                    && (tile & 1 != 0) == need_left
+                    && (tile & 2 != 0) == need_up
+                    && (can_right || tile & 4 == 0)
+                    && (can_down || tile & 8 == 0)
+            })
+            .collect::<Vec<_>>();
+        candidates.shuffle(rng);
+        for tile in candidates {
+            let history_len = self.history.len();
+            self.tiles[idx] = tile;
+            self.counts[tile as usize] -= 1;
+            self.open[idx] = (tile & 4 != 0) as usize + (tile & 8 != 0) as usize;
+            if need_left {
+                self.unite(idx, idx - 1);
+            }
+            if need_up {
+                self.unite(idx, idx - board_size);
+            }
+            let root = self.find(idx);
+            let closed = self.open[root] == 0 && self.size[root] < cell_num - 1;
+            if !closed && self.is_feasible(idx) && self.dfs(idx + 1, rng) {
+                return true;
+            }
+            self.rollback(history_len);
+            self.counts[tile as usize] += 1;
+            self.tiles[idx] = 0;
+            if self.nodes > self.node_limit {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+/// Whether the line of the tile at (h, w) towards `didx` meets the line of its neighbour.
+fn is_matched(board: &Board, h: usize, w: usize, didx: usize) -> bool {
+    let (h_to, w_to) = (
+        h.wrapping_add(Board::DH[didx]),
+        w.wrapping_add(Board::DW[didx]),
+    );
+    h_to < board.board_size
+        && w_to < board.board_size
+        && (board.get(h, w) >> didx) & 1 == 1
+        && (board.get(h_to, w_to) >> ((didx + 2) % 4)) & 1 == 1
+}
+
+/// Matched edges between the tile at `idx` and its neighbours.
+fn matched_edges_around(board: &Board, idx: usize) -> i32 {
+    let board_size = board.board_size;
+    (0..4)
+        .filter(|&didx| is_matched(board, idx / board_size, idx % board_size, didx))
+        .count() as i32
+}
+
+/// Swaps two tiles and returns the change in the number of matched edges.
+fn swap_edge_delta(board: &mut Board, idx1: usize, idx2: usize) -> i32 {
+    let board_size = board.board_size;
+    let (lo, hi) = (usize::min(idx1, idx2), usize::max(idx1, idx2));
+    // The edge between the two tiles is counted from both sides.
+    let shared = |board: &Board| match hi - lo {
+        1 if lo / board_size == hi / board_size => {
+            is_matched(board, lo / board_size, lo % board_size, 2) as i32
+        }
+        d if d == board_size => is_matched(board, lo / board_size, lo % board_size, 3) as i32,
+        _ => 0,
+    };
+    let before = matched_edges_around(board, idx1) + matched_edges_around(board, idx2) - shared(board);
+    board.swap(idx1, idx2);
+    let after = matched_edges_around(board, idx1) + matched_edges_around(board, idx2) - shared(board);
+    after - before
+}
+
+/// The largest connected group of tiles of a board, kept up to date across swaps. A swap that
+/// changes no matched edge of the largest group can only make another group the largest, so only
+/// the groups of the two swapped tiles are searched then. Any other swap searches the whole board.
+struct Components {
+    in_largest: Vec<bool>,
+    largest_size: i32,
+    /// A cell of the largest group after the proposed swap and its size, if it is another group.
+    proposed: Option<(usize, i32)>,
+    /// neighbours[idx][didx]: the cell next to idx towards didx, or !0 off the board.
+    neighbours: Vec<[usize; 4]>,
+    /// seen[idx] == stamp if idx was reached by the current search.
+    seen: Vec<u32>,
+    stamp: u32,
+    stack: Vec<usize>,
+    /// Searches over the whole board so far.
+    full_searches: usize,
+}
+
+impl Components {
+    fn new(board: &Board) -> Components {
+        let board_size = board.board_size;
+        let cell_num = board_size * board_size;
+        let neighbours = (0..cell_num).map(|idx| {
+            let (h, w) = (idx / board_size, idx % board_size);
+            std::array::from_fn(|didx| {
+                let (h_to, w_to) = (h.wrapping_add(Board::DH[didx]), w.wrapping_add(Board::DW[didx]));
+                if h_to < board_size && w_to < board_size { h_to * board_size + w_to } else { !0 }
+            })
+        }).collect();
+        let mut components = Components {
+            in_largest: vec![false; cell_num],
+            largest_size: 0,
+            proposed: None,
+            neighbours,
+            seen: vec![0; cell_num],
+            stamp: 0,
+            stack: vec![],
+            full_searches: 0,
+        };
+        components.proposed = components.search_all(board);
+        components.accept(board);
+        components
+    }
+
+    /// Swaps two tiles and returns the change in the number of matched edges and the size of the
+    /// largest group after the swap, which `accept` makes the current one.
+    fn propose_swap(&mut self, board: &mut Board, idx1: usize, idx2: usize) -> (i32, i32) {
+        let touches_largest = |this: &Components, board: &Board| {
+            [idx1, idx2].into_iter().any(|idx| {
+                (0..4).any(|didx| this.matched(board, idx, didx).is_some_and(|idx_to| this.in_largest[idx_to]))
+            })
+        };
+        let before = touches_largest(self, board);
+        let edge_delta = swap_edge_delta(board, idx1, idx2);
+        self.proposed = if before || touches_largest(self, board) {
+            self.search_all(board)
+        } else {
+            // No edge to the largest group changed, so the groups of the two tiles stay apart
+            // from it.
+            self.stamp += 1;
+            let largest_size = self.largest_size;
+            [idx1, idx2]
+                .into_iter()
+                .map(|idx| (idx, self.search_from(board, idx)))
+                .filter(|&(_, size)| size > largest_size)
+                .max_by_key(|&(_, size)| size)
+        };
+        (edge_delta, self.proposed.map_or(self.largest_size, |(_, size)| size))
+    }
+
+    /// Makes the largest group after the last proposed swap, which `board` has, the current one.
+    fn accept(&mut self, board: &Board) {
+        if let Some((start, size)) = self.proposed.take() {
+            self.stamp += 1;
+            self.search_from(board, start);
+            for (in_largest, &seen) in self.in_largest.iter_mut().zip(&self.seen) {
+                *in_largest = seen == self.stamp;
+            }
+            self.largest_size = size;
+        }
+    }
+
+    /// A cell of the largest group of the whole board and its size.
+    fn search_all(&mut self, board: &Board) -> Option<(usize, i32)> {
+        self.full_searches += 1;
+        self.stamp += 1;
+        (0..board.board_list.len())
+            .map(|start| (start, self.search_from(board, start)))
+            .max_by_key(|&(_, size)| size)
+    }
+
+    /// The neighbour of the tile at `idx` towards `didx`, if their lines meet.
+    fn matched(&self, board: &Board, idx: usize, didx: usize) -> Option<usize> {
+        let idx_to = self.neighbours[idx][didx];
+        let tiles = &board.board_list;
+        (idx_to != !0 && (tiles[idx] >> didx) & 1 == 1 && (tiles[idx_to] >> ((didx + 2) % 4)) & 1 == 1).then_some(idx_to)
+    }
+
+    /// Size of the group of the tile at `start`, or 0 if this search already reached it. The
+    /// empty square belongs to no group.
+    fn search_from(&mut self, board: &Board, start: usize) -> i32 {
+        if self.seen[start] == self.stamp || board.board_list[start] == 0 {
+            return 0;
+        }
+        let mut size = 0;
+        self.seen[start] = self.stamp;
+        self.stack.push(start);
+        while let Some(idx) = self.stack.pop() {
+            size += 1;
+            for didx in 0..4 {
+                let Some(idx_to) = self.matched(board, idx, didx) else {
+                    continue;
+                };
+                if self.seen[idx_to] != self.stamp {
+                    self.seen[idx_to] = self.stamp;
+                    self.stack.push(idx_to);
+                }
+            }
+        }
+        size
+    }
+}
+
+fn annealing(
+    board: &Board,
+    max_iter: usize,
+    movement: Vec<char>,
+    start_temp: f64,
+    params: &Params,
+    budget: &Budget,
+    rng: &mut impl Rng,
+) -> (f32, Vec<char>, f64, Vec<Improvement>) {
+    let mut solution = movement.clone();
+    let mut score = calc_score(board, solution.len(), max_iter);
+    let mut best_solution = movement.clone();
+    let mut best_score = score;
+    let mut iter_num = 0;
+    let config = params.schedule(start_temp.max(params.polish_end_temp), params.polish_end_temp);
+    let mut schedule = Schedule::new(config, budget.slice(f64::min(params.polish_time as f64, budget.remaining())));
+    let mut best_temp = config.start_temp;
+    let mut improvements = vec![];
+    'mainloop: while schedule.advance() {
+        iter_num += 1;
+        let mut new_board = board.clone();
+        let mut new_solution = solution.clone();
+        let selection = if iter_num == 1 { 6 } else { rng.gen_range(0, 6) };
+        match selection {
+            0 => {
+                if new_solution.len() < max_iter / 2 {
+                    continue;
+                }
+                let select1 = rng.gen_range(0, new_solution.len() - 1);
+                let select2 = rng.gen_range(select1 + 1, new_solution.len());
+                new_solution.swap(select1, select2);
+            }
+            1 => {
+                if new_solution.is_empty() {
+                    continue;
+                }
+                let select = rng.gen_range(0, new_solution.len());
+                let random_dchar = Board::DCHARS[rng.gen_range(0, 4)];
+                new_solution[select] = random_dchar;
+            }
+            2 => {
+                if new_solution.len() < max_iter / 2 {
+                    continue;
+                }
+                let select = rng.gen_range(0, new_solution.len());
+                new_solution.remove(select);
+            }
+            3 => {
+                if new_solution.is_empty() || new_solution.len() >= max_iter {
+                    continue;
+                }
+                let select = rng.gen_range(0, new_solution.len());
+                let random_dchar = Board::DCHARS[rng.gen_range(0, 4)];
+                new_solution.insert(select, random_dchar);
+            }
+            4 => {
+                if new_solution.len() >= max_iter {
+                    continue;
+                }
+                let random_dchar = Board::DCHARS[rng.gen_range(0, 4)];
+                new_solution.push(random_dchar);
+            }
+            5 => {
+                new_solution.pop();
+            }
+            6 => {
+                // pass
+            }
+            _ => unreachable!(),
+        }
+        for &dchar in &new_solution {
+            if !new_board.move_empty_tile(dchar) {
+                continue 'mainloop;
+            }
+        }
+        let new_score = calc_score(&new_board, new_solution.len(), max_iter);
+        if iter_num == 1 && new_score < 4.5e5 {
+            budget.report(EventKind::Done, "polish", iter_num, new_score as f64, new_solution.len());
+            return (new_score, new_solution, schedule.temp(), improvements);
+        }
+        if schedule.accept((new_score - score) as f64, rng) {
+            score = new_score;
+            solution = new_solution.clone();
+        }
+        if new_score > best_score {
+            best_score = new_score;
+            best_solution = solution.clone();
+            best_temp = schedule.temp();
+            budget.report(EventKind::Best, "polish", iter_num, best_score as f64, best_solution.len());
+            improvements.push(Improvement { time: budget.elapsed(), score: best_score as f64, moves: best_solution.len() });
+        }
+    }
+    eprintln!("BEST SCORE = {}", best_score);
+    eprintln!("ITER = {}", iter_num);
+    schedule.report("annealing");
+    budget.report(EventKind::Done, "polish", iter_num, best_score as f64, best_solution.len());
+    (best_score, best_solution, best_temp, improvements)
+}
+
+fn calc_score(board: &Board, iter: usize, max_iter: usize) -> f32 {
+    let board_size = board.board_size;
+    let mut passed = vec![false; board_size * board_size];
+    let mut que = VecDeque::new();
+    let mut max_tree_size = 0;
+    let mut score = 0.0;
+    for h_st in 0..board_size {
+        for w_st in 0..board_size {
+            if !passed[h_st * board_size + w_st] {
+                let mut tree_size = 0;
+                passed[h_st * board_size + w_st] = true;
+                que.push_back(((h_st, w_st), 10));
+                while let Some(((h_now, w_now), prev)) = que.pop_front() {
+                    tree_size += 1;
+                    for didx in 0..4 {
+                        if prev == (didx + 2) % 4 {
+                            continue;
+                        }
+                        if ((board.get(h_now, w_now) >> didx) & 1) == 1 {
+                            let (h_to, w_to) = (
+                                h_now.wrapping_add(Board::DH[didx]),
+                                w_now.wrapping_add(Board::DW[didx]),
+                            );
+                            if h_to >= board_size || w_to >= board_size {
+                                continue;
+                            }
+                            if ((board.get(h_to, w_to) >> ((didx + 2) % 4)) & 1) == 1
+                                && !passed[h_to * board_size + w_to]
+                            {
+                                passed[h_to * board_size + w_to] = true;
+                                que.push_back(((h_to, w_to), didx));
+                            }
+                        }
+                    }
+                }
+                max_tree_size = i32::max(max_tree_size, tree_size);
+            }
+        }
+    }
+    if max_tree_size == (board_size * board_size - 1) as i32 {
+        score += 5e5 * (2.0 - (iter as f32 / max_iter as f32));
+    } else {
+        score += 5e5 * (max_tree_size as f32) / (board_size * board_size - 1) as f32;
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_input;
+
+    /// Evaluations per second of a release build, roughly, so that a counted budget of
+    /// `search_time` seconds is about as many swaps as the contest run gets.
+    const EVALS_PER_SEC: u64 = 1_000_000;
+
+    /// Each restart of the pipeline searches for `search_time` seconds, and about half of the
+    /// searches on in/0000 find a full tree.
+    #[test]
+    fn tile_swap_search_finds_full_trees() {
+        let input = parse_input(include_str!("../in/0000.txt"));
+        let params = Params::default();
+        let board = Board::from_input(&input);
+        let full_trees = (0..8)
+            .filter(|&seed| {
+                let budget = Budget::new(params.search_time(input.n) as f64, EVALS_PER_SEC);
+                let mut rng = rand_pcg::Pcg64Mcg::new(seed);
+                let target = annealing_search_best_board(&board, input.T, budget, &params, &mut rng);
+                calc_score(&target, 0, input.T) > 5e5 && is_reachable(&board, &target)
+            })
+            .count();
+        assert!(full_trees >= 4, "{} of 8 searches found a full tree", full_trees);
+    }
+}
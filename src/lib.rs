@@ -25,6 +25,17 @@ macro_rules! mat {
 	($e:expr; $d:expr $(; $ds:expr)+) => { Vec::from(vec![mat![$e $(; $ds)*]; $d]) };
 }
 
+pub mod beam_search;
+pub mod budget;
+pub mod greedy;
+pub mod move_annealing;
+pub mod observer;
+pub mod params;
+pub mod schedule;
+pub mod solver;
+pub mod tile_macro;
+pub mod trace;
+
 use std::cell::Cell;
 
 #[derive(Clone, Debug)]
@@ -106,6 +117,21 @@ pub fn parse_output(_input: &Input, f: &str) -> Result<Output, String> {
 	Ok(f.trim().chars().collect())
 }
 
+/// Reads a previous answer from `path` for use as a starting point, checking with `Sim` that
+/// every move is legal and that there are at most `T` of them.
+pub fn read_answer(input: &Input, path: &str) -> Result<Output, String> {
+	let f = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+	let out = parse_output(input, &f)?;
+	let mut sim = Sim::new(input);
+	for &c in &out {
+		sim.apply(c)?;
+	}
+	if out.len() > input.T {
+		return Err(format!("too many moves: {} > {}", out.len(), input.T));
+	}
+	Ok(out)
+}
+
 pub struct Sim {
 	n: usize,
 	T: usize,
@@ -115,6 +141,7 @@ pub struct Sim {
 	j: usize
 }
 
+#[allow(clippy::needless_range_loop)]
 impl Sim {
 	pub fn new(input: &Input) -> Self {
 		let mut i = !0;
@@ -140,8 +167,9 @@ impl Sim {
 	}
 	pub fn apply(&mut self, c: char) -> Result<(), String> {
 		if let Some(d) = DIR.iter().position(|&d| d == c) {
-			let i2 = self.i + DIJ[d].0;
-			let j2 = self.j + DIJ[d].1;
+			// Moving off the top or left edge wraps around to a huge index, also in debug builds.
+			let i2 = self.i.wrapping_add(DIJ[d].0);
+			let j2 = self.j.wrapping_add(DIJ[d].1);
 			if i2 >= self.n || j2 >= self.n {
 				Err(format!("illegal move: {} (turn {})", c, self.turn))
 			} else {
@@ -159,6 +187,28 @@ impl Sim {
 		}
 	}
 	pub fn compute_score(&self, input: &Input) -> (i64, String, Vec<Vec<bool>>) {
+		let (uf, max_tree) = self.largest_tree(input);
+		let mut bs = mat![false; self.n; self.n];
+		if max_tree != !0 {
+			for i in 0..self.n {
+				for j in 0..self.n {
+					bs[i][j] = uf.same(max_tree, i * self.n + j);
+				}
+			}
+		}
+		if self.turn > self.T {
+			return (0, "too many moves".to_owned(), bs);
+		}
+		let size = if max_tree == !0 {
+			0
+		} else {
+			uf.size(max_tree)
+		};
+		(self.score(size), String::new(), bs)
+	}
+	/// The tiles joined by their lines, and a cell of the largest tree among them or `!0` if
+	/// there is none.
+	fn largest_tree(&self, input: &Input) -> (UnionFind, usize) {
 		let mut uf = UnionFind::new(self.n * self.n);
 		let mut tree = vec![true; self.n * self.n];
 		let mut tiles = mat![0; self.n; self.n];
@@ -196,38 +246,43 @@ impl Sim {
 		let mut max_tree = !0;
 		for i in 0..self.n {
 			for j in 0..self.n {
-				if tiles[i][j] != 0 && tree[uf.find(i * self.n + j)] {
-					if max_tree == !0 || uf.size(max_tree) < uf.size(i * self.n + j) {
-						max_tree = i * self.n + j;
-					}
+				if tiles[i][j] != 0 && tree[uf.find(i * self.n + j)] && (max_tree == !0 || uf.size(max_tree) < uf.size(i * self.n + j)) {
+					max_tree = i * self.n + j;
 				}
 			}
 		}
-		let mut bs = mat![false; self.n; self.n];
-		if max_tree != !0 {
-			for i in 0..self.n {
-				for j in 0..self.n {
-					bs[i][j] = uf.same(max_tree, i * self.n + j);
-				}
-			}
+		(uf, max_tree)
+	}
+	fn largest_tree_size(&self, input: &Input) -> usize {
+		match self.largest_tree(input) {
+			(_, max_tree) if max_tree == !0 => 0,
+			(uf, max_tree) => uf.size(max_tree),
 		}
+	}
+	/// The score at this turn with a largest tree of `size` tiles.
+	fn score(&self, size: usize) -> i64 {
 		if self.turn > self.T {
-			return (0, format!("too many moves"), bs);
-		}
-		let size = if max_tree == !0 {
 			0
+		} else if size == self.n * self.n - 1 {
+			(500000.0 * (1.0 + (self.T - self.turn) as f64 / self.T as f64)).round() as i64
 		} else {
-			uf.size(max_tree)
-		};
-		let score = if size == self.n * self.n - 1 {
-			(500000.0 * (1.0 + (self.T - self.turn) as f64 / self.T as f64)).round()
-		} else {
-			(500000.0 * size as f64 / (self.n * self.n - 1) as f64).round()
-		} as i64;
-		(score, String::new(), bs)
+			(500000.0 * size as f64 / (self.n * self.n - 1) as f64).round() as i64
+		}
+	}
+	/// Whether a line of `tile`, placed at (i, j), meets one of a neighbour other than the one at
+	/// `skip`.
+	fn meets_neighbour(&self, input: &Input, tile: usize, (i, j): (usize, usize), skip: (usize, usize)) -> bool {
+		(0..4).any(|d| {
+			let (i2, j2) = (i.wrapping_add(DIJ[d].0), j.wrapping_add(DIJ[d].1));
+			i2 < self.n && j2 < self.n && (i2, j2) != skip && tile >> d & 1 == 1 && {
+				let (x, y) = self.from[i2][j2];
+				input.tiles[x][y] >> ((d + 2) % 4) & 1 == 1
+			}
+		})
 	}
 }
 
+#[allow(clippy::type_complexity)]
 pub fn compute_score(input: &Input, out: &[char]) -> (i64, String, (Vec<Vec<(usize, usize)>>, Vec<Vec<bool>>)) {
 	let mut sim = Sim::new(input);
 	for &c in out {
@@ -239,9 +294,72 @@ pub fn compute_score(input: &Input, out: &[char]) -> (i64, String, (Vec<Vec<(usi
 	(score, err, (sim.from.clone(), tree))
 }
 
+/// Score after every turn: entry `k` is the score once the first `k` moves of `out` are made.
+/// The trees are only found again, at `O(n^2)`, after a move that takes the moved tile's lines
+/// away from a neighbour's or to one. The curve stops at the first illegal move.
+pub fn score_curve(input: &Input, out: &[char]) -> Vec<i64> {
+	let mut sim = Sim::new(input);
+	let mut size = sim.largest_tree_size(input);
+	let mut curve = vec![sim.score(size)];
+	for &c in out {
+		let empty = (sim.i, sim.j);
+		if sim.apply(c).is_err() {
+			break;
+		}
+		// The tile moved from where the empty square is now to where it was.
+		let moved = (sim.i, sim.j);
+		let (x, y) = sim.from[empty.0][empty.1];
+		let tile = input.tiles[x][y];
+		if sim.meets_neighbour(input, tile, moved, empty) || sim.meets_neighbour(input, tile, empty, moved) {
+			size = sim.largest_tree_size(input);
+		}
+		curve.push(sim.score(size));
+	}
+	curve
+}
+
+/// Length of the shortest prefix of `out` with the highest score.
+pub fn best_prefix(input: &Input, out: &[char]) -> usize {
+	best_prefix_of_curve(&score_curve(input, out))
+}
+
+/// `best_prefix` for a curve already computed by `score_curve`.
+pub fn best_prefix_of_curve(curve: &[i64]) -> usize {
+	(0..curve.len()).max_by_key(|&k| (curve[k], std::cmp::Reverse(k))).unwrap()
+}
+
+/// Replays the answer `solver` is about to print. If it has an illegal move or more than `T` moves,
+/// which only a bug in the solver can cause, it is cut to its best legal prefix, or replaced by
+/// the best prefix of one of the `earlier` answers if that scores higher, with a warning. Doing
+/// nothing is always legal, so the result never scores 0 for being illegal.
+pub fn guard_answer(input: &Input, solver: &str, out: Output, earlier: &[&[char]]) -> Output {
+	let (_, err, _) = compute_score(input, &out);
+	if err.is_empty() {
+		return out;
+	}
+	eprintln!("WARNING: {} gave an illegal answer of {} moves: {}", solver, out.len(), err);
+	let mut best = out;
+	best.truncate(best_prefix(input, &best));
+	let (mut best_score, mut source) = (compute_score(input, &best).0, "its best legal prefix");
+	for candidate in earlier {
+		let curve = score_curve(input, candidate);
+		let len = best_prefix_of_curve(&curve);
+		if curve[len] > best_score {
+			(best_score, best, source) = (curve[len], candidate[..len].to_vec(), "an earlier answer");
+		}
+	}
+	eprintln!("WARNING: printing {} instead: {} moves with score {}", source, best.len(), best_score);
+	best
+}
+
 pub fn gen(seed: u64) -> Input {
+	gen_with_size(seed, (6 + seed % 5) as usize)
+}
+
+/// Like `gen`, but for any board size `n >= 2` rather than the contest's 6 to 10.
+#[allow(clippy::needless_range_loop)]
+pub fn gen_with_size(seed: u64, n: usize) -> Input {
 	let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
-	let n = (6 + seed % 5) as usize;
 	let T = 2 * n * n * n;
 	let mut tiles = mat![0; n; n];
 	let mut edges = vec![];
@@ -381,3 +499,181 @@ r#"<filter id="wood" x="-30%" y="-30%" width="160%" height="160%">
 	}
 	(score, error, doc.to_string())
 }
+
+/// The target board of `trace`, with each cell shaded by the number of turns the greedy router
+/// took to place it and labelled with their range, followed by tables of the steps and of the
+/// improvements of the annealing. Cells whose tile differs on the final board after `out` are
+/// outlined in red.
+pub fn vis_trace(input: &Input, out: &[char], trace: &trace::Trace) -> String {
+	const W: usize = 80;
+	let mut html = String::new();
+	if let Some(target) = &trace.target {
+		let n = target.len();
+		let (_, _, (from, _)) = compute_score(input, out);
+		let mut doc = svg::Document::new().set("id", "target").set("viewBox", (-5, -5, W * n + 10, W * n + 10)).set("width", W * n + 10).set("height", W * n + 10);
+		doc = doc.add(rect(-5, -5, (W * n + 10) as i32, (W * n + 10) as i32, "white"));
+		let max_turns = trace.steps.iter().map(|step| step.turns.len()).max().unwrap_or(0).max(1);
+		for i in 0..n {
+			for j in 0..n {
+				let step = trace.steps.iter().find(|step| step.cell == (i, j));
+				let mut title = format!("({}, {})", i, j);
+				let mut g = Group::new();
+				if let Some(step) = step {
+					title += &format!("\nturns {}..{} ({} moves)", step.turns.start, step.turns.end, step.turns.len());
+					let opacity = step.turns.len() as f64 / max_turns as f64;
+					g = g.add(rect((j * W) as i32, (i * W) as i32, W as i32, W as i32, "orange").set("fill-opacity", opacity));
+				}
+				let color = "#905020";
+				let (cx, cy) = (j * W + W / 2, i * W + W / 2);
+				let tile = target[i][j];
+				if tile == 0 {
+					g = g.add(rect((j * W) as i32, (i * W) as i32, W as i32, W as i32, "lightgray"));
+				}
+				for (bit, (dx, dy)) in [(1, (-1, 0)), (2, (0, -1)), (4, (1, 0)), (8, (0, 1))] {
+					if tile & bit != 0 {
+						let (x2, y2) = (cx as i32 + dx * (W / 2) as i32, cy as i32 + dy * (W / 2) as i32);
+						g = g.add(Line::new().set("x1", cx).set("y1", cy).set("x2", x2).set("y2", y2).set("stroke", color).set("stroke-width", W / 4));
+					}
+				}
+				if tile != 0 {
+					g = g.add(Circle::new().set("cx", cx).set("cy", cy).set("r", W / 8).set("fill", color));
+				}
+				if n == input.n && input.tiles[from[i][j].0][from[i][j].1] != tile {
+					title += "\ndiffers on the final board";
+					g = g.add(rect((j * W) as i32 + 2, (i * W) as i32 + 2, W as i32 - 4, W as i32 - 4, "none").set("stroke", "red").set("stroke-width", 3));
+				}
+				if let Some(step) = step {
+					g = g.add(Text::new(format!(r#"<text x="{}" y="{}" font-size="13">{}-{}</text>"#, j * W + 3, i * W + 14, step.turns.start, step.turns.end)));
+				}
+				doc = doc.add(g.add(Title::new().add(Text::new(title))));
+			}
+		}
+		html += &doc.to_string();
+	}
+	if !trace.steps.is_empty() {
+		html += "<h3>Greedy steps</h3><table><tr><th>cell</th><th>turns</th><th>moves</th></tr>";
+		for step in &trace.steps {
+			html += &format!("<tr><td>({}, {})</td><td>{}..{}</td><td>{}</td></tr>", step.cell.0, step.cell.1, step.turns.start, step.turns.end, step.turns.len());
+		}
+		html += "</table>";
+	}
+	if !trace.improvements.is_empty() {
+		html += "<h3>Annealing improvements</h3><table><tr><th>time</th><th>score</th><th>moves</th></tr>";
+		for improvement in &trace.improvements {
+			html += &format!("<tr><td>{:.3}</td><td>{}</td><td>{}</td></tr>", improvement.time, improvement.score, improvement.moves);
+		}
+		html += "</table>";
+	}
+	html
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn input() -> Input {
+		parse_input(include_str!("../in/0000.txt"))
+	}
+
+	/// `len` random legal moves from the start of `input`.
+	fn random_walk(input: &Input, len: usize, seed: u64) -> Output {
+		let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+		let mut sim = Sim::new(input);
+		let mut out = vec![];
+		while out.len() < len {
+			let c = DIR[rng.gen_range(0, 4)];
+			if sim.apply(c).is_ok() {
+				out.push(c);
+			}
+		}
+		out
+	}
+
+	/// Appends moves up to the top row to `out`, and then one more, which is illegal.
+	fn push_illegal_move(input: &Input, out: &mut Output) {
+		let mut sim = Sim::new(input);
+		for &c in out.iter() {
+			sim.apply(c).unwrap();
+		}
+		out.extend(std::iter::repeat_n('U', sim.i + 1));
+	}
+
+	#[test]
+	fn score_curve_matches_compute_score_past_t() {
+		let input = input();
+		let out = random_walk(&input, input.T + 20, 0);
+		let curve = score_curve(&input, &out);
+		assert_eq!(curve.len(), out.len() + 1);
+		for (k, &score) in curve.iter().enumerate() {
+			assert_eq!(score, compute_score(&input, &out[..k]).0, "after {} moves", k);
+		}
+		assert!(curve[input.T + 1..].iter().all(|&score| score == 0));
+	}
+
+	#[test]
+	fn score_curve_matches_compute_score_on_full_trees() {
+		// A full tree of three tiles, broken by every other move.
+		let input = Input { n: 2, T: 3, tiles: vec![vec![12, 1], vec![2, 0]] };
+		let out = ['L', 'R', 'L', 'R', 'L'];
+		let curve = score_curve(&input, &out);
+		for (k, &score) in curve.iter().enumerate() {
+			assert_eq!(score, compute_score(&input, &out[..k]).0, "after {} moves", k);
+		}
+		assert_eq!(curve[2], 666667);
+	}
+
+	#[test]
+	fn score_curve_stops_at_an_illegal_move() {
+		let input = input();
+		let mut out = random_walk(&input, 30, 1);
+		push_illegal_move(&input, &mut out);
+		let legal = out.len() - 1;
+		out.extend(random_walk(&input, 10, 2));
+		assert_eq!(score_curve(&input, &out).len(), legal + 1);
+	}
+
+	#[test]
+	fn best_prefix_of_curve_is_the_shortest_best() {
+		assert_eq!(best_prefix_of_curve(&[3]), 0);
+		assert_eq!(best_prefix_of_curve(&[3, 5, 4, 5, 2]), 1);
+		assert_eq!(best_prefix_of_curve(&[7, 5, 0]), 0);
+	}
+
+	#[test]
+	fn guard_answer_keeps_a_legal_answer() {
+		let input = input();
+		let out = random_walk(&input, 50, 3);
+		assert_eq!(guard_answer(&input, "test", out.clone(), &[]), out);
+	}
+
+	#[test]
+	fn guard_answer_cuts_an_illegal_move() {
+		let input = input();
+		let mut out = random_walk(&input, 50, 4);
+		push_illegal_move(&input, &mut out);
+		let guarded = guard_answer(&input, "test", out.clone(), &[]);
+		assert_eq!(guarded, out[..best_prefix(&input, &out[..out.len() - 1])]);
+		assert!(compute_score(&input, &guarded).1.is_empty());
+	}
+
+	#[test]
+	fn guard_answer_cuts_more_than_t_moves() {
+		let input = input();
+		let out = random_walk(&input, input.T + 5, 5);
+		let guarded = guard_answer(&input, "test", out.clone(), &[]);
+		assert!(guarded.len() <= input.T);
+		assert_eq!(guarded, out[..best_prefix(&input, &out)]);
+	}
+
+	#[test]
+	fn guard_answer_prefers_a_better_earlier_answer() {
+		let input = input();
+		let mut out = vec![];
+		push_illegal_move(&input, &mut out);
+		let earlier = random_walk(&input, 100, 6);
+		let len = best_prefix(&input, &earlier);
+		let out_len = best_prefix(&input, &out[..out.len() - 1]);
+		assert!(compute_score(&input, &earlier[..len]).0 > compute_score(&input, &out[..out_len]).0, "no better earlier answer");
+		assert_eq!(guard_answer(&input, "test", out, &[&earlier]), earlier[..len]);
+	}
+}
@@ -0,0 +1,431 @@
+//! Annealing of the moves themselves: a route is mutated by inserting, removing and rerouting
+//! moves, and scored by the largest tree it leaves.
+
+use rand::prelude::*;
+use std::collections::VecDeque;
+use crate::budget::Budget;
+use crate::observer::EventKind;
+use crate::params::Params;
+use crate::schedule::Schedule;
+use crate::solver::{Polished, Polisher, Solver};
+use crate::trace::Improvement;
+use crate::{best_prefix, Input, Output};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct Board {
+    board_size: usize,
+    board_list: Vec<u8>,
+    empty_block_area: (usize, usize)
+}
+
+#[allow(dead_code)]
+impl Board {
+    const DCHARS: [char; 4] = ['L', 'U', 'R', 'D'];
+    const DH: [usize; 4] = [0, !0, 0, 1];
+    const DW: [usize; 4] = [!0, 0, 1, 0]; //LURD
+    fn new(board_size: usize, board_list: Vec<u8>) -> Board {
+        let mut empty_block_area = (0, 0);
+        for h in 0..board_size {
+            for w in 0..board_size {
+                if board_list[h * board_size + w] == 0 {
+                    empty_block_area = (h, w);
+                }
+            }
+        }
+        Board {
+            board_size,
+            board_list,
+            empty_block_area
+        }
+    }
+
+    fn from_input(input: &Input) -> Board {
+        Board::new(input.n, input.tiles.iter().flatten().map(|&tile| tile as u8).collect())
+    }
+
+    fn from_dchar_to_didx(dchar: char) -> usize {
+        match dchar {
+            'L' => 0,
+            'U' => 1,
+            'R' => 2,
+            'D' => 3,
+            _ => unreachable!()
+        }
+    }
+
+    fn move_empty_block(&mut self, didx: usize) -> bool {
+        let (th, tw) = (
+            self.empty_block_area.0.wrapping_add(Self::DH[didx]),
+            self.empty_block_area.1.wrapping_add(Self::DW[didx]),
+        );
+        if th >= self.board_size || tw >= self.board_size {
+            return false;
+        }
+        self.swap(self.empty_block_area, (th, tw));
+        self.empty_block_area = (th, tw);
+        true
+    }
+
+    fn move_left(&mut self) -> bool {
+        self.move_empty_block(0)
+    }
+
+    fn move_up(&mut self) -> bool {
+        self.move_empty_block(1)
+    }
+
+    fn move_right(&mut self) -> bool {
+        self.move_empty_block(2)
+    }
+
+    fn move_down(&mut self) -> bool {
+        self.move_empty_block(3)
+    }
+
+    fn swap(&mut self, (h1, w1): (usize, usize), (h2, w2): (usize, usize)) {
+        self.board_list
+            .swap(h1 * self.board_size + w1, h2 * self.board_size + w2);
+    }
+
+    fn replace(&mut self, h: usize, w: usize, value: u8) {
+        self.board_list[h * self.board_size + w] = value;
+    }
+
+    fn get(&self, h: usize, w: usize) -> u8 {
+        self.board_list[h * self.board_size + w]
+    }
+
+    fn print_board(&self) {
+        for h in 0..self.board_size {
+            let mut bytes = vec![];
+            for w in 0..self.board_size {
+                bytes.push(format!("{:x}", self.board_list[h * self.board_size + w]));
+            }
+            println!("{}", bytes.into_iter().collect::<String>());
+        }
+    }
+}
+
+/// Mutations of a move sequence that never send the empty block off the board.
+#[derive(Debug, Clone, Copy)]
+enum Mutation {
+    /// Inserts a move immediately followed by its inverse.
+    InsertInversePair,
+    /// Inserts a 2x2 rotation, which cycles three tiles and returns the empty block.
+    InsertCycle,
+    /// Removes a 2x2 rotation already present in the sequence.
+    RemoveCycle,
+    /// Replaces a segment with another route between the same two empty block positions.
+    Reroute,
+}
+
+impl Mutation {
+    const ALL: [Mutation; 4] = [
+        Mutation::InsertInversePair,
+        Mutation::InsertCycle,
+        Mutation::RemoveCycle,
+        Mutation::Reroute,
+    ];
+    const MAX_REROUTE_LEN: usize = 16;
+
+    /// Mutates `solution` in place. Returns false if the mutation can not be applied.
+    fn apply(
+        self,
+        board: &Board,
+        max_iter: usize,
+        solution: &mut Vec<char>,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let board_size = board.board_size;
+        let trail = empty_block_trail(board, solution);
+        match self {
+            Mutation::InsertInversePair => {
+                if solution.len() + 2 > max_iter {
+                    return false;
+                }
+                let select = rng.gen_range(0, solution.len() + 1);
+                let didxs = movable_didxs(board_size, trail[select]);
+                let didx = *didxs.choose(rng).unwrap();
+                solution.splice(
+                    select..select,
+                    [Board::DCHARS[didx], Board::DCHARS[(didx + 2) % 4]],
+                );
+            }
+            Mutation::InsertCycle => {
+                if solution.len() + 4 > max_iter {
+                    return false;
+                }
+                let select = rng.gen_range(0, solution.len() + 1);
+                let didxs = movable_didxs(board_size, trail[select]);
+                let mut cycles = vec![];
+                for &didx1 in &didxs {
+                    for &didx2 in &didxs {
+                        if didx1 % 2 != didx2 % 2 {
+                            cycles.push([didx1, didx2, (didx1 + 2) % 4, (didx2 + 2) % 4]);
+                        }
+                    }
+                }
+                let cycle = cycles.choose(rng).unwrap();
+                solution.splice(select..select, cycle.iter().map(|&didx| Board::DCHARS[didx]));
+            }
+            Mutation::RemoveCycle => {
+                let candidates = (0..solution.len().saturating_sub(3))
+                    .filter(|&i| is_cycle(&solution[i..i + 4]))
+                    .collect::<Vec<_>>();
+                if let Some(&select) = candidates.choose(rng) {
+                    solution.drain(select..select + 4);
+                } else {
+                    return false;
+                }
+            }
+            Mutation::Reroute => {
+                if solution.is_empty() {
+                    return false;
+                }
+                let select1 = rng.gen_range(0, solution.len());
+                let select2 = rng.gen_range(
+                    select1 + 1,
+                    usize::min(solution.len(), select1 + Self::MAX_REROUTE_LEN) + 1,
+                );
+                let (from, to) = (trail[select1], trail[select2]);
+                let via = (
+                    rng.gen_range(
+                        usize::min(from.0, to.0).saturating_sub(1),
+                        usize::min(usize::max(from.0, to.0) + 1, board_size - 1) + 1,
+                    ),
+                    rng.gen_range(
+                        usize::min(from.1, to.1).saturating_sub(1),
+                        usize::min(usize::max(from.1, to.1) + 1, board_size - 1) + 1,
+                    ),
+                );
+                let mut route = random_monotone_route(from, via, rng);
+                route.extend(random_monotone_route(via, to, rng));
+                if route[..] == solution[select1..select2]
+                    || solution.len() - (select2 - select1) + route.len() > max_iter
+                {
+                    return false;
+                }
+                solution.splice(select1..select2, route);
+            }
+        }
+        true
+    }
+}
+
+/// Positions of the empty block before the first move and after every move.
+fn empty_block_trail(board: &Board, solution: &[char]) -> Vec<(usize, usize)> {
+    let mut trail = Vec::with_capacity(solution.len() + 1);
+    let mut pos = board.empty_block_area;
+    trail.push(pos);
+    for &dchar in solution {
+        let didx = Board::from_dchar_to_didx(dchar);
+        pos = (
+            pos.0.wrapping_add(Board::DH[didx]),
+            pos.1.wrapping_add(Board::DW[didx]),
+        );
+        trail.push(pos);
+    }
+    trail
+}
+
+fn movable_didxs(board_size: usize, (h, w): (usize, usize)) -> Vec<usize> {
+    (0..4)
+        .filter(|&didx| {
+            h.wrapping_add(Board::DH[didx]) < board_size
+                && w.wrapping_add(Board::DW[didx]) < board_size
+        })
+        .collect()
+}
+
+fn is_cycle(movement: &[char]) -> bool {
+    let didxs = movement
+        .iter()
+        .map(|&dchar| Board::from_dchar_to_didx(dchar))
+        .collect::<Vec<_>>();
+    didxs[0] % 2 != didxs[1] % 2
+        && didxs[2] == (didxs[0] + 2) % 4
+        && didxs[3] == (didxs[1] + 2) % 4
+}
+
+fn random_monotone_route(
+    from: (usize, usize),
+    to: (usize, usize),
+    rng: &mut impl Rng,
+) -> Vec<char> {
+    let mut route = vec![];
+    route.extend(std::iter::repeat_n('U', from.0.saturating_sub(to.0)));
+    route.extend(std::iter::repeat_n('D', to.0.saturating_sub(from.0)));
+    route.extend(std::iter::repeat_n('L', from.1.saturating_sub(to.1)));
+    route.extend(std::iter::repeat_n('R', to.1.saturating_sub(from.1)));
+    route.shuffle(rng);
+    route
+}
+
+/// Annealing of the moves themselves, as a whole solver or as the polishing stage of a pipeline.
+pub struct RerouteAnnealing<'a> {
+    pub params: &'a Params,
+}
+
+impl RerouteAnnealing<'_> {
+    /// Anneals `out`, such as a previous answer, until `budget` is over. Returns the best route
+    /// and the improvements on the way.
+    pub fn anneal(&self, input: &Input, out: Output, budget: &Budget, rng: &mut impl Rng) -> (Output, Vec<Improvement>) {
+        let board = Board::from_input(input);
+        let start_temp = self.params.anneal_start_temp;
+        let (solution, _, improvements) = annealing(input.T, &board, out, start_temp, self.params, budget.clone(), rng);
+        (solution, improvements)
+    }
+}
+
+/// Anneals a route from scratch if `warm_start` is empty.
+impl Solver for RerouteAnnealing<'_> {
+    fn solve_from(&self, input: &Input, warm_start: Output, budget: &Budget, rng: &mut impl Rng) -> Output {
+        let (mut solution, _) = self.anneal(input, warm_start, budget, rng);
+        solution.truncate(best_prefix(input, &solution));
+        solution
+    }
+}
+
+/// Polishes for `polish_time` seconds, starting from the route given.
+impl Polisher for RerouteAnnealing<'_> {
+    fn polish(&self, input: &Input, out: Output, temp: Option<f64>, budget: &Budget, rng: &mut impl Rng) -> Polished {
+        let board = Board::from_input(input);
+        let start_temp = temp.unwrap_or(self.params.anneal_start_temp);
+        let budget = budget.slice(f64::min(self.params.polish_time as f64, budget.remaining()));
+        let (out, temp, improvements) = annealing(input.T, &board, out, start_temp, self.params, budget, rng);
+        Polished { out, temp, improvements }
+    }
+}
+
+/// Anneals `movement` from `start_temp`. Returns the best route, the temperature it was found at
+/// and the improvements on the way.
+fn annealing(
+    max_iter: usize,
+    board: &Board,
+    movement: Vec<char>,
+    start_temp: f64,
+    params: &Params,
+    budget: Budget,
+    rng: &mut impl Rng,
+) -> (Vec<char>, f64, Vec<Improvement>) {
+    let mut solution = movement.clone();
+    let mut start_board = board.clone();
+    for &dchar in &movement {
+        start_board.move_empty_block(Board::from_dchar_to_didx(dchar));
+    }
+    let mut score = calc_score(&start_board);
+    let mut best_solution = movement.clone();
+    let mut best_score = score;
+    let mut improvements = vec![];
+    let mut iter_num = 0;
+    let mut proposed = [0; Mutation::ALL.len()];
+    let mut accepted = [0; Mutation::ALL.len()];
+    let config = params.schedule(start_temp.max(params.anneal_end_temp), params.anneal_end_temp);
+    let mut schedule = Schedule::new(config, budget);
+    let mut best_temp = config.start_temp;
+    'mainloop: while schedule.advance() {
+        iter_num += 1;
+        let mut new_board = board.clone();
+        let mut new_solution = solution.clone();
+        let mutation = Mutation::ALL[rng.gen_range(0, Mutation::ALL.len())];
+        if !mutation.apply(board, max_iter, &mut new_solution, rng) {
+            continue;
+        }
+        proposed[mutation as usize] += 1;
+        for &dchar in &new_solution {
+            match dchar {
+                'L' => {
+                    if !new_board.move_left() {
+                        continue 'mainloop;
+                    };
+                }
+                'U' => {
+                    if !new_board.move_up() {
+                        continue 'mainloop;
+                    };
+                }
+                'D' => {
+                    if !new_board.move_down() {
+                        continue 'mainloop;
+                    };
+                }
+                'R' => {
+                    if !new_board.move_right() {
+                        continue 'mainloop;
+                    };
+                }
+                _ => unreachable!(),
+            }
+        }
+        let new_score = calc_score(&new_board);
+        if schedule.accept((new_score - score) as f64, rng) {
+            score = new_score;
+            solution = new_solution.clone();
+            accepted[mutation as usize] += 1;
+        }
+        if new_score > best_score {
+            best_score = new_score;
+            best_solution = solution.clone();
+            best_temp = schedule.temp();
+            schedule.budget().report(EventKind::Best, "annealing", iter_num, best_score as f64, best_solution.len());
+            improvements.push(Improvement {
+                time: schedule.budget().elapsed(),
+                score: best_score as f64,
+                moves: best_solution.len(),
+            });
+        }
+    }
+    eprintln!("BEST SCORE = {}", best_score);
+    eprintln!("ITER = {}", iter_num);
+    schedule.report("annealing");
+    schedule.budget().report(EventKind::Done, "annealing", iter_num, best_score as f64, best_solution.len());
+    for mutation in Mutation::ALL {
+        let (p, a) = (proposed[mutation as usize], accepted[mutation as usize]);
+        eprintln!(
+            "{:?}: accepted {} / {} ({:.1}%)",
+            mutation,
+            a,
+            p,
+            if p == 0 { 0.0 } else { 100.0 * a as f32 / p as f32 }
+        );
+    }
+    (best_solution, best_temp, improvements)
+}
+
+fn calc_score(board: &Board) -> f32 {
+    let board_size = board.board_size;
+    let mut seen = vec![vec![false; board_size]; board_size];
+    let mut que = VecDeque::new();
+    let mut max_tree_size = 0;
+    for h_st in 0..board_size {
+        for w_st in 0..board_size {
+            if !seen[h_st][w_st] {
+                let mut tree_size = 0;
+                seen[h_st][w_st] = true;
+                que.push_back((h_st, w_st));
+                while let Some((h_now, w_now)) = que.pop_front() {
+                    tree_size += 1;
+                    for didx in 0..4 {
+                        if ((board.get(h_now, w_now) >> didx) & 1) == 1 {
+                            let (h_to, w_to) = (
+                                h_now.wrapping_add(Board::DH[didx]),
+                                w_now.wrapping_add(Board::DW[didx]),
+                            );
+                            if h_to >= board_size || w_to >= board_size {
+                                continue;
+                            }
+                            if ((board.get(h_to, w_to) >> ((didx + 2) % 4)) & 1) == 1
+                                && !seen[h_to][w_to]
+                            {
+                                seen[h_to][w_to] = true;
+                                que.push_back((h_to, w_to));
+                            }
+                        }
+                    }
+                }
+                max_tree_size = i32::max(max_tree_size, tree_size);
+            }
+        }
+    }
+    5e5 * max_tree_size as f32 / (board_size * board_size - 1) as f32
+}
@@ -0,0 +1,97 @@
+//! Progress reports of the solvers, for plotting how they converge and where the time goes.
+//!
+//! Stages report through their `Budget`, which stamps each event with its clock and passes it to
+//! the observer given by `Budget::with_observer`, if any. `JsonLines` writes the events to a file.
+
+use std::io::Write;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+	/// The best score of the stage went up.
+	Best,
+	/// The stage finished.
+	Done,
+}
+
+impl EventKind {
+	pub fn name(self) -> &'static str {
+		match self {
+			EventKind::Best => "best",
+			EventKind::Done => "done",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event<'a> {
+	/// Seconds on the clock of the budget, so counted evaluations if the budget counts them.
+	pub time: f64,
+	pub kind: EventKind,
+	pub stage: &'a str,
+	/// Iterations the stage has done.
+	pub iteration: usize,
+	/// The best score of the stage so far, by its own measure. Stages that search for a target
+	/// board score the board, not a route.
+	pub score: f64,
+	/// Length of the route the score is for, or 0 for a target board.
+	pub moves: usize,
+}
+
+pub trait Observer: Send + Sync {
+	fn observe(&self, event: &Event);
+}
+
+/// Writes each event as a line of JSON.
+pub struct JsonLines {
+	out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLines {
+	pub fn new(out: impl Write + Send + 'static) -> JsonLines {
+		JsonLines { out: Mutex::new(Box::new(out)) }
+	}
+
+	pub fn create(path: &str) -> std::io::Result<JsonLines> {
+		Ok(JsonLines::new(std::io::BufWriter::new(std::fs::File::create(path)?)))
+	}
+}
+
+fn escape(text: &str) -> String {
+	let mut escaped = String::new();
+	for c in text.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// JSON has no infinities or NaN.
+fn number(value: f64) -> String {
+	if value.is_finite() {
+		value.to_string()
+	} else {
+		"null".to_owned()
+	}
+}
+
+impl Observer for JsonLines {
+	fn observe(&self, event: &Event) {
+		let line = format!(
+			"{{\"time\":{},\"event\":\"{}\",\"stage\":\"{}\",\"iteration\":{},\"score\":{},\"moves\":{}}}",
+			number(event.time),
+			event.kind.name(),
+			escape(event.stage),
+			event.iteration,
+			number(event.score),
+			event.moves
+		);
+		let mut out = self.out.lock().unwrap();
+		// Losing telemetry must not cost the answer.
+		let _ = writeln!(out, "{}", line).and_then(|_| out.flush());
+	}
+}
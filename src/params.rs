@@ -0,0 +1,299 @@
+//! Tuning knobs of the solvers, overridable at run time.
+//!
+//! The defaults are the values the solvers were tuned with. A solver overrides them, lowest
+//! precedence first, from a `key=value` file given by `--params FILE`, from `AHC011_<KEY>`
+//! environment variables and from `--<key>=<value>` arguments. Lists are comma separated.
+
+use crate::budget::Budget;
+use crate::schedule::{Clock, Cooling, ScheduleConfig};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// How the greedy pipeline searches the target board of each restart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetSearch {
+	/// Tile-swap annealing, with the constructive search as a fallback.
+	TileSwap,
+	/// Spanning-tree walk, with the above as a fallback.
+	SpanningTree,
+}
+
+impl FromStr for TargetSearch {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<TargetSearch, ()> {
+		match s {
+			"tile_swap" => Ok(TargetSearch::TileSwap),
+			"spanning_tree" => Ok(TargetSearch::SpanningTree),
+			_ => Err(()),
+		}
+	}
+}
+
+impl Display for TargetSearch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			TargetSearch::TileSwap => "tile_swap",
+			TargetSearch::SpanningTree => "spanning_tree",
+		})
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Params {
+	pub seed: u128,
+	/// Seconds the whole run may take.
+	pub duration: f32,
+	/// If nonzero, a second of every time budget is this many evaluations instead of wall time,
+	/// which makes runs reproducible.
+	pub evals_per_sec: u64,
+	/// Seconds of target search per restart of the greedy pipeline, by board size.
+	pub search_time: Vec<f32>,
+	/// Beam width of the beam search, by board size.
+	pub beam_width: Vec<usize>,
+	/// Seconds the move annealing of the greedy pipeline polishes each route for.
+	pub polish_time: f32,
+	/// Turns of the final score one cell of tile travel is assumed to cost in the target search.
+	pub route_weight: f32,
+	pub target_search: TargetSearch,
+	pub search_start_temp: f64,
+	pub search_end_temp: f64,
+	pub tree_start_temp: f64,
+	pub tree_end_temp: f64,
+	pub polish_start_temp: f64,
+	pub polish_end_temp: f64,
+	pub anneal_start_temp: f64,
+	pub anneal_end_temp: f64,
+	/// Schedule of every annealer, whose temperatures are the ones above.
+	pub cooling: Cooling,
+	pub reheats: usize,
+	pub clock: Clock,
+}
+
+impl Default for Params {
+	fn default() -> Params {
+		Params {
+			seed: 0,
+			duration: 2.8,
+			evals_per_sec: 0,
+			search_time: vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.2, 0.3, 0.4, 0.5],
+			beam_width: vec![0, 0, 0, 0, 0, 0, 3400, 1700, 950, 690, 450],
+			polish_time: 0.15,
+			route_weight: 0.1,
+			target_search: TargetSearch::TileSwap,
+			search_start_temp: 500.0,
+			search_end_temp: 5.0,
+			tree_start_temp: 10.0,
+			tree_end_temp: 0.5,
+			polish_start_temp: 1000.0,
+			polish_end_temp: 5.0,
+			anneal_start_temp: 2000.0,
+			anneal_end_temp: 5.0,
+			cooling: Cooling::Linear,
+			reheats: 0,
+			clock: Clock::Time,
+		}
+	}
+}
+
+pub const KEYS: [&str; 19] = [
+	"seed",
+	"duration",
+	"evals_per_sec",
+	"search_time",
+	"beam_width",
+	"polish_time",
+	"route_weight",
+	"target_search",
+	"search_start_temp",
+	"search_end_temp",
+	"tree_start_temp",
+	"tree_end_temp",
+	"polish_start_temp",
+	"polish_end_temp",
+	"anneal_start_temp",
+	"anneal_end_temp",
+	"cooling",
+	"reheats",
+	"clock",
+];
+
+/// Keys whose value is a table indexed by board size.
+pub const TABLES: [&str; 2] = ["search_time", "beam_width"];
+
+/// Past the end of its table, the search time grows linearly with the board size...
+const SEARCH_TIME_EXPONENT: f64 = 1.0;
+/// ...and the beam width shrinks with the cube of it, as the number of turns grows.
+const BEAM_WIDTH_EXPONENT: f64 = -3.0;
+
+/// Entry `n` of `table`. Leading zeros are placeholders for sizes the table was not tuned for:
+/// sizes before the first nonzero entry scale it by `(n / first) ^ exponent`, as sizes past the
+/// end scale the last entry by `(n / last) ^ exponent`.
+fn by_size(table: &[f64], n: usize, exponent: f64) -> f64 {
+	let first = table.iter().position(|&v| v != 0.0).unwrap_or(0);
+	let last = table.len() - 1;
+	let scale = |k: usize| table[k] * (n.max(1) as f64 / k.max(1) as f64).powf(exponent);
+	if n < first {
+		scale(first)
+	} else if n > last {
+		scale(last)
+	} else {
+		table[n]
+	}
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+	value.trim().parse().map_err(|_| format!("invalid value for {}: {}", key, value))
+}
+
+fn parse_list<T: FromStr>(key: &str, value: &str) -> Result<Vec<T>, String> {
+	value.split(',').map(|v| parse(key, v)).collect()
+}
+
+fn join<T: Display>(list: &[T]) -> String {
+	list.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+impl Params {
+	/// The budget of a whole run.
+	pub fn budget(&self) -> Budget {
+		Budget::new(self.duration as f64, self.evals_per_sec)
+	}
+
+	/// The schedule of an annealer that cools from `start_temp` to `end_temp`.
+	pub fn schedule(&self, start_temp: f64, end_temp: f64) -> ScheduleConfig {
+		ScheduleConfig { start_temp, end_temp, cooling: self.cooling, reheats: self.reheats, clock: self.clock }
+	}
+
+	/// Seconds of target search per restart for board size `n`.
+	pub fn search_time(&self, n: usize) -> f32 {
+		let table = self.search_time.iter().map(|&v| v as f64).collect::<Vec<_>>();
+		by_size(&table, n, SEARCH_TIME_EXPONENT) as f32
+	}
+
+	/// Beam width for board size `n`.
+	pub fn beam_width(&self, n: usize) -> usize {
+		let table = self.beam_width.iter().map(|&v| v as f64).collect::<Vec<_>>();
+		(by_size(&table, n, BEAM_WIDTH_EXPONENT).round() as usize).max(1)
+	}
+
+	/// Extends the tables up to board size `n` with the values they imply.
+	pub fn extend_tables(&mut self, n: usize) {
+		while self.search_time.len() <= n {
+			self.search_time.push(self.search_time(self.search_time.len()));
+		}
+		while self.beam_width.len() <= n {
+			self.beam_width.push(self.beam_width(self.beam_width.len()));
+		}
+	}
+
+	/// Sets the parameter `key`, in which `-` may stand for `_`.
+	pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+		match key.trim().replace('-', "_").as_str() {
+			"seed" => self.seed = parse(key, value)?,
+			"duration" => self.duration = parse(key, value)?,
+			"evals_per_sec" => self.evals_per_sec = parse(key, value)?,
+			"search_time" => self.search_time = parse_list(key, value)?,
+			"beam_width" => self.beam_width = parse_list(key, value)?,
+			"polish_time" => self.polish_time = parse(key, value)?,
+			"route_weight" => self.route_weight = parse(key, value)?,
+			"target_search" => self.target_search = parse(key, value)?,
+			"search_start_temp" => self.search_start_temp = parse(key, value)?,
+			"search_end_temp" => self.search_end_temp = parse(key, value)?,
+			"tree_start_temp" => self.tree_start_temp = parse(key, value)?,
+			"tree_end_temp" => self.tree_end_temp = parse(key, value)?,
+			"polish_start_temp" => self.polish_start_temp = parse(key, value)?,
+			"polish_end_temp" => self.polish_end_temp = parse(key, value)?,
+			"anneal_start_temp" => self.anneal_start_temp = parse(key, value)?,
+			"anneal_end_temp" => self.anneal_end_temp = parse(key, value)?,
+			"cooling" => self.cooling = parse(key, value)?,
+			"reheats" => self.reheats = parse(key, value)?,
+			"clock" => self.clock = parse(key, value)?,
+			_ => return Err(format!("unknown parameter: {}", key)),
+		}
+		Ok(())
+	}
+
+	pub fn get(&self, key: &str) -> Option<String> {
+		Some(match key {
+			"seed" => self.seed.to_string(),
+			"duration" => self.duration.to_string(),
+			"evals_per_sec" => self.evals_per_sec.to_string(),
+			"search_time" => join(&self.search_time),
+			"beam_width" => join(&self.beam_width),
+			"polish_time" => self.polish_time.to_string(),
+			"route_weight" => self.route_weight.to_string(),
+			"target_search" => self.target_search.to_string(),
+			"search_start_temp" => self.search_start_temp.to_string(),
+			"search_end_temp" => self.search_end_temp.to_string(),
+			"tree_start_temp" => self.tree_start_temp.to_string(),
+			"tree_end_temp" => self.tree_end_temp.to_string(),
+			"polish_start_temp" => self.polish_start_temp.to_string(),
+			"polish_end_temp" => self.polish_end_temp.to_string(),
+			"anneal_start_temp" => self.anneal_start_temp.to_string(),
+			"anneal_end_temp" => self.anneal_end_temp.to_string(),
+			"cooling" => self.cooling.to_string(),
+			"reheats" => self.reheats.to_string(),
+			"clock" => self.clock.to_string(),
+			_ => return None,
+		})
+	}
+
+	/// Applies the `key=value` lines of `text`. Blank lines and lines starting with `#` are
+	/// skipped.
+	pub fn apply(&mut self, text: &str) -> Result<(), String> {
+		for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+			let (key, value) = line.split_once('=').ok_or_else(|| format!("expected key=value: {}", line))?;
+			self.set(key, value)?;
+		}
+		Ok(())
+	}
+
+	/// Applies the overrides of the file, environment and arguments to these defaults. Returns the
+	/// parameters and the arguments that are not about them, for the solver to parse.
+	pub fn load(mut self, args: impl Iterator<Item = String>) -> Result<(Params, Vec<String>), String> {
+		let args = args.collect::<Vec<_>>();
+		if let Some(k) = args.iter().position(|arg| arg == "--params") {
+			let path = args.get(k + 1).ok_or("--params needs a file")?;
+			let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+			self.apply(&text)?;
+		}
+		for key in KEYS {
+			if let Ok(value) = std::env::var(format!("AHC011_{}", key.to_uppercase())) {
+				self.set(key, &value)?;
+			}
+		}
+		let mut rest = vec![];
+		let mut args = args.into_iter();
+		while let Some(arg) = args.next() {
+			if arg == "--params" {
+				args.next();
+			} else if let Some((key, value)) = arg.strip_prefix("--").and_then(|arg| arg.split_once('=')) {
+				self.set(key, value)?;
+			} else {
+				rest.push(arg);
+			}
+		}
+		Ok((self, rest))
+	}
+
+	/// Like `load` with the command line, but exits with the error instead of returning it.
+	/// Solvers echo the parameters they end up with to stderr, so that every run can be
+	/// reproduced.
+	pub fn from_args(self) -> (Params, Vec<String>) {
+		self.load(std::env::args().skip(1)).unwrap_or_else(|err| {
+			eprintln!("{}", err);
+			std::process::exit(1);
+		})
+	}
+}
+
+/// The parameters as a file `Params::apply` reads back.
+impl Display for Params {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for key in KEYS {
+			writeln!(f, "{}={}", key, self.get(key).unwrap())?;
+		}
+		Ok(())
+	}
+}
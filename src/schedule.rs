@@ -0,0 +1,177 @@
+//! Temperature schedules shared by the annealers.
+
+use crate::budget::Budget;
+use rand::Rng;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Number of slices of the run the acceptance statistics are split into.
+const STAT_BUCKETS: usize = 10;
+/// Iterations between readings of a wall clock, which cost more than an iteration of the cheaper
+/// annealers. A counted budget is read every iteration.
+const CLOCK_STRIDE: usize = 16;
+
+/// How the temperature falls from `start_temp` to `end_temp` within a period.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cooling {
+	Linear,
+	Exponential,
+	/// Falls quickly at first and slowly towards the end.
+	Log,
+}
+
+/// What the progress of a run is measured by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clock {
+	/// Elapsed time, by the clock of the budget, over the duration of the run.
+	Time,
+	/// Iterations done over the given number. The duration of the run still bounds it.
+	Iter(usize),
+}
+
+impl FromStr for Cooling {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Cooling, ()> {
+		match s {
+			"linear" => Ok(Cooling::Linear),
+			"exponential" => Ok(Cooling::Exponential),
+			"log" => Ok(Cooling::Log),
+			_ => Err(()),
+		}
+	}
+}
+
+impl Display for Cooling {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Cooling::Linear => "linear",
+			Cooling::Exponential => "exponential",
+			Cooling::Log => "log",
+		})
+	}
+}
+
+/// `time`, or `iter:N` for `Iter(N)`.
+impl FromStr for Clock {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Clock, ()> {
+		match s.split_once(':') {
+			None if s == "time" => Ok(Clock::Time),
+			Some(("iter", max_iter)) => max_iter.parse().map(Clock::Iter).map_err(|_| ()),
+			_ => Err(()),
+		}
+	}
+}
+
+impl Display for Clock {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Clock::Time => f.write_str("time"),
+			Clock::Iter(max_iter) => write!(f, "iter:{}", max_iter),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScheduleConfig {
+	pub start_temp: f64,
+	pub end_temp: f64,
+	pub cooling: Cooling,
+	/// The run is split into `reheats + 1` periods, each cooling from `start_temp` again.
+	pub reheats: usize,
+	pub clock: Clock,
+}
+
+pub struct Schedule {
+	config: ScheduleConfig,
+	budget: Budget,
+	start: f64,
+	duration: f64,
+	elapsed: f64,
+	iter: usize,
+	progress: f64,
+	temp: f64,
+	/// (proposed, accepted) per slice of the run.
+	stats: [(usize, usize); STAT_BUCKETS],
+}
+
+impl Schedule {
+	/// A schedule that runs until `budget` is over, counting each iteration as an evaluation.
+	pub fn new(config: ScheduleConfig, budget: Budget) -> Schedule {
+		Schedule {
+			config,
+			start: budget.elapsed(),
+			duration: budget.remaining(),
+			elapsed: 0.0,
+			budget,
+			iter: 0,
+			progress: 0.0,
+			temp: config.start_temp,
+			stats: [(0, 0); STAT_BUCKETS],
+		}
+	}
+	/// Starts the next iteration and updates the temperature. Returns false once the run is over.
+	pub fn advance(&mut self) -> bool {
+		self.budget.tick();
+		if self.budget.is_counted() || self.iter.is_multiple_of(CLOCK_STRIDE) {
+			self.elapsed = self.budget.elapsed() - self.start;
+		}
+		if self.elapsed > self.duration {
+			return false;
+		}
+		self.progress = match self.config.clock {
+			Clock::Time => self.elapsed / self.duration,
+			Clock::Iter(max_iter) => {
+				if self.iter >= max_iter {
+					return false;
+				}
+				self.iter as f64 / max_iter as f64
+			}
+		};
+		self.iter += 1;
+		let periods = (self.config.reheats + 1) as f64;
+		let t = if self.progress >= 1.0 { 1.0 } else { (self.progress * periods).fract() };
+		let (start, end) = (self.config.start_temp, self.config.end_temp);
+		self.temp = match self.config.cooling {
+			Cooling::Linear => start + (end - start) * t,
+			Cooling::Exponential => start * (end / start).powf(t),
+			Cooling::Log => start / (1.0 + (start / end - 1.0) * (1.0 + (std::f64::consts::E - 1.0) * t).ln()),
+		};
+		true
+	}
+	pub fn iter(&self) -> usize {
+		self.iter
+	}
+	pub fn progress(&self) -> f64 {
+		self.progress
+	}
+	pub fn temp(&self) -> f64 {
+		self.temp
+	}
+	pub fn budget(&self) -> &Budget {
+		&self.budget
+	}
+	/// Decides whether to accept a change of `delta` in a score being maximized.
+	pub fn accept<R: Rng>(&mut self, delta: f64, rng: &mut R) -> bool {
+		let accepted = (delta / self.temp).exp() > rng.gen::<f64>();
+		let bucket = &mut self.stats[((self.progress * STAT_BUCKETS as f64) as usize).min(STAT_BUCKETS - 1)];
+		bucket.0 += 1;
+		if accepted {
+			bucket.1 += 1;
+		}
+		accepted
+	}
+	/// Prints the acceptance ratio of each slice of the run to stderr.
+	pub fn report(&self, name: &str) {
+		let ratios = self.stats.iter().map(|&(proposed, accepted)| {
+			if proposed == 0 {
+				"-".to_owned()
+			} else {
+				format!("{:.3}", accepted as f64 / proposed as f64)
+			}
+		}).collect::<Vec<_>>();
+		eprintln!("{} acceptance ({} iter, {:?}) = {}", name, self.iter, self.config.cooling, ratios.join(" "));
+	}
+}
@@ -0,0 +1,151 @@
+//! Interfaces the solvers implement, so that they can be run the same way and their stages
+//! combined.
+//!
+//! A solver either implements `Solver` as a whole or is split into a `TargetSearch`, a `Router`
+//! and a `Polisher`, which `Pipeline` restarts until the budget is over. Stages work on the
+//! library types, so any search, router and polisher fit together. A caller that needs more
+//! control, such as running restarts on several threads, drives `Pipeline::restart` itself.
+
+use crate::budget::Budget;
+use crate::observer::EventKind;
+use crate::trace::{Improvement, Step, Trace};
+use crate::{best_prefix_of_curve, compute_score, score_curve, Input, Output};
+use rand::Rng;
+
+/// An arrangement of tiles, in the layout of `Input::tiles`.
+pub type Tiles = Vec<Vec<usize>>;
+
+pub trait Solver {
+	/// Searches on from `warm_start`, such as a previous answer, or from scratch if it has no
+	/// moves, until `budget` is over.
+	fn solve_from(&self, input: &Input, warm_start: Output, budget: &Budget, rng: &mut impl Rng) -> Output;
+
+	fn solve(&self, input: &Input, budget: &Budget, rng: &mut impl Rng) -> Output {
+		self.solve_from(input, vec![], budget, rng)
+	}
+}
+
+/// Picks the arrangement of tiles a route should end in.
+pub trait TargetSearch {
+	/// Returns `None` if no target was found. It should only do so after spending some of
+	/// `budget`, as the pipeline then simply tries again.
+	fn search(&self, input: &Input, budget: &Budget, rng: &mut impl Rng) -> Option<Tiles>;
+}
+
+/// Finds the moves that turn the input into a target arrangement.
+pub trait Router {
+	/// Returns the moves, or the ones made before getting stuck if the target was not reached.
+	/// The turns that placed each cell are added to `steps`, if the router knows them.
+	fn route(&self, input: &Input, target: &Tiles, steps: &mut Vec<Step>, budget: &Budget, rng: &mut impl Rng) -> Output;
+}
+
+/// A route after polishing.
+pub struct Polished {
+	pub out: Output,
+	/// Temperature the polisher found `out` at, where polishing it again should start.
+	pub temp: f64,
+	pub improvements: Vec<Improvement>,
+}
+
+/// Improves a route of at most `T` moves.
+pub trait Polisher {
+	/// Starts at temperature `temp` if given, such as when polishing a previous answer again.
+	fn polish(&self, input: &Input, out: Output, temp: Option<f64>, budget: &Budget, rng: &mut impl Rng) -> Polished;
+}
+
+/// The best answer so far of a pipeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Best {
+	pub score: i64,
+	/// Cut to its best prefix.
+	pub out: Output,
+	/// Temperature the polisher found `out` at, or `None` if it was not polished.
+	pub temp: Option<f64>,
+	/// How `out` came about.
+	pub trace: Trace,
+}
+
+impl Best {
+	/// Doing nothing.
+	pub fn new(input: &Input) -> Best {
+		Best { score: compute_score(input, &[]).0, out: vec![], temp: None, trace: Trace::default() }
+	}
+
+	/// Keeps the best prefix of `out` if it scores higher. Returns the score of that prefix.
+	pub fn offer(&mut self, input: &Input, out: &[char], temp: Option<f64>, trace: Trace) -> i64 {
+		let curve = score_curve(input, out);
+		let len = best_prefix_of_curve(&curve);
+		if curve[len] > self.score {
+			*self = Best { score: curve[len], out: out[..len].to_vec(), temp, trace };
+		}
+		curve[len]
+	}
+
+	pub fn merge(&mut self, other: &Best) {
+		if other.score > self.score {
+			*self = other.clone();
+		}
+	}
+}
+
+/// Searches a target, routes to it and polishes the route, restarting until the budget is over.
+/// Routes longer than `T` are not polished. The answer is the best-scoring prefix of any route,
+/// polished or not.
+///
+/// Reports the score of every route as a `route` stage, and its best answer as `pipeline`.
+pub struct Pipeline<S, R, P> {
+	pub search: S,
+	pub router: R,
+	pub polisher: P,
+}
+
+impl<S: TargetSearch, R: Router, P: Polisher> Pipeline<S, R, P> {
+	/// Routes to `target`, or to a freshly searched target, polishes the route and keeps the
+	/// answer in `best` if it is better. Returns the target, or `None` if the search found none.
+	pub fn restart(&self, input: &Input, target: Option<Tiles>, best: &mut Best, budget: &Budget, rng: &mut impl Rng) -> Option<Tiles> {
+		let target = match target {
+			Some(target) => target,
+			None => self.search.search(input, budget, rng)?,
+		};
+		let mut steps = vec![];
+		let out = self.router.route(input, &target, &mut steps, budget, rng);
+		let trace = Trace { target: Some(target.clone()), steps, improvements: vec![] };
+		let score = best.offer(input, &out, None, trace.clone());
+		budget.report(EventKind::Done, "route", 0, score as f64, out.len());
+		if out.len() <= input.T {
+			let polished = self.polisher.polish(input, out, None, budget, rng);
+			let trace = Trace { improvements: polished.improvements, ..trace };
+			best.offer(input, &polished.out, Some(polished.temp), trace);
+		}
+		Some(target)
+	}
+
+	/// Keeps a previous answer in `best`, then polishes it further from temperature `temp`, or
+	/// from the start if not given.
+	pub fn warm_start(&self, input: &Input, out: Output, temp: Option<f64>, best: &mut Best, budget: &Budget, rng: &mut impl Rng) {
+		best.offer(input, &out, temp, Trace::default());
+		let polished = self.polisher.polish(input, out, temp, budget, rng);
+		let trace = Trace { improvements: polished.improvements, ..Trace::default() };
+		best.offer(input, &polished.out, Some(polished.temp), trace);
+	}
+}
+
+/// Starts with a polish of `warm_start` if it has any moves.
+impl<S: TargetSearch, R: Router, P: Polisher> Solver for Pipeline<S, R, P> {
+	fn solve_from(&self, input: &Input, warm_start: Output, budget: &Budget, rng: &mut impl Rng) -> Output {
+		let mut best = Best::new(input);
+		if !warm_start.is_empty() {
+			self.warm_start(input, warm_start, None, &mut best, budget, rng);
+		}
+		let mut restarts = 0;
+		while !budget.is_over() {
+			restarts += 1;
+			let last_score = best.score;
+			self.restart(input, None, &mut best, budget, rng);
+			if best.score > last_score {
+				budget.report(EventKind::Best, "pipeline", restarts, best.score as f64, best.out.len());
+			}
+		}
+		best.out
+	}
+}
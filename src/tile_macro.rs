@@ -0,0 +1,385 @@
+//! Sliding-move macros that permute a few tiles and leave every other tile in place.
+//!
+//! The tiles are first carried into a 2x3 (or 3x2) window, permuted there by an exhaustive
+//! search over the window, and carried back by replaying the transport in reverse. The empty
+//! square ends where it started.
+
+use crate::{DIJ, DIR};
+use std::collections::{HashMap, VecDeque};
+
+/// Number of windows closest to the tiles that are tried before giving up.
+const MAX_WINDOWS: usize = 8;
+
+/// Cells `[top, bottom) x [left, right)` the empty square may move in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+	pub top: usize,
+	pub left: usize,
+	pub bottom: usize,
+	pub right: usize,
+}
+
+impl Region {
+	pub fn new(top: usize, left: usize, bottom: usize, right: usize) -> Region {
+		Region { top, left, bottom, right }
+	}
+	pub fn whole(n: usize) -> Region {
+		Region::new(0, 0, n, n)
+	}
+	pub fn height(&self) -> usize {
+		self.bottom - self.top
+	}
+	pub fn width(&self) -> usize {
+		self.right - self.left
+	}
+	pub fn contains(&self, (i, j): (usize, usize)) -> bool {
+		self.top <= i && i < self.bottom && self.left <= j && j < self.right
+	}
+	fn index(&self, (i, j): (usize, usize)) -> usize {
+		(i - self.top) * self.width() + j - self.left
+	}
+	fn cell(&self, k: usize) -> (usize, usize) {
+		(self.top + k / self.width(), self.left + k % self.width())
+	}
+	fn cells(&self) -> Vec<(usize, usize)> {
+		(0..self.height() * self.width()).map(|k| self.cell(k)).collect()
+	}
+	fn step(&self, (i, j): (usize, usize), d: usize) -> Option<(usize, usize)> {
+		let p = (i.wrapping_add(DIJ[d].0), j.wrapping_add(DIJ[d].1));
+		if self.contains(p) {
+			Some(p)
+		} else {
+			None
+		}
+	}
+	fn distance(&self, (i, j): (usize, usize)) -> usize {
+		let di = if i < self.top { self.top - i } else { (i + 1).saturating_sub(self.bottom) };
+		let dj = if j < self.left { self.left - j } else { (j + 1).saturating_sub(self.right) };
+		di + dj
+	}
+	/// All 2x3 and 3x2 windows inside the region.
+	fn windows(&self) -> Vec<Region> {
+		let mut windows = vec![];
+		for (h, w) in [(2, 3), (3, 2)] {
+			if self.height() < h || self.width() < w {
+				continue;
+			}
+			for i in self.top..=self.bottom - h {
+				for j in self.left..=self.right - w {
+					windows.push(Region::new(i, j, i + h, j + w));
+				}
+			}
+		}
+		windows
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Macro {
+	pub moves: Vec<char>,
+}
+
+impl Macro {
+	/// Number of moves the macro takes.
+	pub fn cost(&self) -> usize {
+		self.moves.len()
+	}
+}
+
+/// Moves the tile at `cells[0]` to `cells[1]`, `cells[1]` to `cells[2]` and `cells[2]` to `cells[0]`.
+pub fn cycle3(region: &Region, empty: (usize, usize), cells: [(usize, usize); 3]) -> Option<Macro> {
+	permute(region, empty, &cells, &[cells[1], cells[2], cells[0]])
+}
+
+/// Swaps the tiles of both pairs at once.
+///
+/// Four tiles are harder to gather in a narrow region than three, so the swap is also tried as
+/// the 3-cycles `a -> b -> c -> a` and `c -> a -> d -> c`, and the cheaper result is returned.
+pub fn swap2(region: &Region, empty: (usize, usize), pairs: [((usize, usize), (usize, usize)); 2]) -> Option<Macro> {
+	let [(a, b), (c, d)] = pairs;
+	let direct = permute(region, empty, &[a, b, c, d], &[b, a, d, c]);
+	let split = cycle3(region, empty, [a, b, c]).and_then(|first| {
+		let second = cycle3(region, empty, [c, a, d])?;
+		Some(Macro { moves: [first.moves, second.moves].concat() })
+	});
+	match (direct, split) {
+		(Some(direct), Some(split)) => Some(if direct.cost() <= split.cost() { direct } else { split }),
+		(direct, split) => direct.or(split),
+	}
+}
+
+/// Moves the tile at `from[k]` to `to[k]` for every `k` without moving the empty square at `empty`
+/// or any other tile. `to` must be an even permutation of `from`, and the region must be at least
+/// 2x3 or 3x2 and contain every cell involved.
+pub fn permute(region: &Region, empty: (usize, usize), from: &[(usize, usize)], to: &[(usize, usize)]) -> Option<Macro> {
+	if region.height() < 2 || region.width() < 2 || region.height() * region.width() < 6 || from.len() != to.len() || from.len() > 4 {
+		return None;
+	}
+	if !region.contains(empty) || from.iter().any(|&p| !region.contains(p) || p == empty) {
+		return None;
+	}
+	if (0..from.len()).any(|k| from[..k].contains(&from[k]) || to[..k].contains(&to[k])) {
+		return None;
+	}
+	let mut sigma = vec![];
+	for q in to {
+		sigma.push(from.iter().position(|p| p == q)?);
+	}
+	let mut seen = vec![false; from.len()];
+	let mut transpositions = 0;
+	for k in 0..from.len() {
+		if seen[k] {
+			continue;
+		}
+		let mut x = k;
+		while !seen[x] {
+			seen[x] = true;
+			x = sigma[x];
+			transpositions += 1;
+		}
+		transpositions -= 1;
+	}
+	if transpositions % 2 != 0 {
+		return None;
+	}
+	let mut windows = region.windows();
+	windows.sort_by_key(|w| from.iter().map(|&p| w.distance(p)).sum::<usize>() + w.distance(empty));
+	let mut best: Option<Macro> = None;
+	for window in windows.iter().take(MAX_WINDOWS) {
+		for order in orders(from.len()) {
+			if let Some(moves) = permute_via(region, window, empty, from, &sigma, &order) {
+				if best.as_ref().is_none_or(|m| m.cost() > moves.len()) {
+					best = Some(Macro { moves: moves.into_iter().map(|d| DIR[d]).collect() });
+				}
+			}
+		}
+	}
+	best
+}
+
+/// Carries the tiles into `window`, permutes them there and carries them back.
+/// The tiles are carried in the given `order`, each to the nearest window cell still unused.
+fn permute_via(region: &Region, window: &Region, mut empty: (usize, usize), from: &[(usize, usize)], sigma: &[usize], order: &[usize]) -> Option<Vec<usize>> {
+	let mut pos = from.to_vec();
+	let mut targets = vec![(0, 0); pos.len()];
+	let mut free = window.cells();
+	for &k in order {
+		let t = (0..free.len()).min_by_key(|&t| dist(pos[k], free[t]))?;
+		targets[k] = free.remove(t);
+	}
+	let mut blocked = vec![false; region.height() * region.width()];
+	let mut transport = vec![];
+	for &k in order {
+		for d in route_tile(region, &blocked, pos[k], empty, targets[k])? {
+			let next = region.step(empty, d).unwrap();
+			for p in pos.iter_mut() {
+				if *p == next {
+					*p = empty;
+				}
+			}
+			empty = next;
+			transport.push(d);
+		}
+		blocked[region.index(targets[k])] = true;
+	}
+	for d in route_empty(region, &blocked, empty, &free)? {
+		empty = region.step(empty, d).unwrap();
+		transport.push(d);
+	}
+	let mut moves = transport.clone();
+	moves.extend(solve_window(window, empty, &targets, sigma)?);
+	moves.extend(transport.iter().rev().map(|&d| (d + 2) % 4));
+	Some(moves)
+}
+
+/// Shortest moves of the empty square that bring the tile at `tile` to `goal`.
+fn route_tile(region: &Region, blocked: &[bool], tile: (usize, usize), empty: (usize, usize), goal: (usize, usize)) -> Option<Vec<usize>> {
+	let size = region.height() * region.width();
+	let start = region.index(tile) * size + region.index(empty);
+	let mut prev = vec![None; size * size];
+	let mut que = VecDeque::new();
+	prev[start] = Some((start, !0));
+	que.push_back(start);
+	while let Some(s) = que.pop_front() {
+		let (t, e) = (region.cell(s / size), region.cell(s % size));
+		if t == goal {
+			let mut path = vec![];
+			let mut s = s;
+			while s != start {
+				let (p, d) = prev[s].unwrap();
+				path.push(d);
+				s = p;
+			}
+			path.reverse();
+			return Some(path);
+		}
+		for d in 0..4 {
+			if let Some(e2) = region.step(e, d) {
+				if blocked[region.index(e2)] {
+					continue;
+				}
+				let t2 = if e2 == t { e } else { t };
+				let s2 = region.index(t2) * size + region.index(e2);
+				if prev[s2].is_none() {
+					prev[s2] = Some((s, d));
+					que.push_back(s2);
+				}
+			}
+		}
+	}
+	None
+}
+
+/// Shortest moves of the empty square to any of `goals`.
+fn route_empty(region: &Region, blocked: &[bool], empty: (usize, usize), goals: &[(usize, usize)]) -> Option<Vec<usize>> {
+	let mut prev = vec![None; region.height() * region.width()];
+	let mut que = VecDeque::new();
+	prev[region.index(empty)] = Some((empty, !0));
+	que.push_back(empty);
+	while let Some(p) = que.pop_front() {
+		if goals.contains(&p) {
+			let mut path = vec![];
+			let mut p = p;
+			while p != empty {
+				let (q, d) = prev[region.index(p)].unwrap();
+				path.push(d);
+				p = q;
+			}
+			path.reverse();
+			return Some(path);
+		}
+		for d in 0..4 {
+			if let Some(q) = region.step(p, d) {
+				if !blocked[region.index(q)] && prev[region.index(q)].is_none() {
+					prev[region.index(q)] = Some((p, d));
+					que.push_back(q);
+				}
+			}
+		}
+	}
+	None
+}
+
+/// Breadth-first search over every arrangement of the window for the moves that send the tile at
+/// `targets[k]` to `targets[sigma[k]]` and return everything else, including the empty square.
+fn solve_window(window: &Region, empty: (usize, usize), targets: &[(usize, usize)], sigma: &[usize]) -> Option<Vec<usize>> {
+	let size = window.height() * window.width();
+	let mut start = (0..size).map(|k| (targets.len() + 1 + k) as u8).collect::<Vec<_>>();
+	start[window.index(empty)] = 0;
+	let mut goal = start.clone();
+	for (k, &t) in targets.iter().enumerate() {
+		start[window.index(t)] = k as u8 + 1;
+		goal[window.index(targets[sigma[k]])] = k as u8 + 1;
+	}
+	let mut prev = HashMap::new();
+	let mut que = VecDeque::new();
+	prev.insert(start.clone(), (start.clone(), !0));
+	que.push_back(start.clone());
+	while let Some(state) = que.pop_front() {
+		if state == goal {
+			let mut path = vec![];
+			let mut state = state;
+			while state != start {
+				let (p, d) = prev[&state].clone();
+				path.push(d);
+				state = p;
+			}
+			path.reverse();
+			return Some(path);
+		}
+		let e = window.cell(state.iter().position(|&x| x == 0).unwrap());
+		for d in 0..4 {
+			if let Some(e2) = window.step(e, d) {
+				let mut next = state.clone();
+				next.swap(window.index(e), window.index(e2));
+				if !prev.contains_key(&next) {
+					prev.insert(next.clone(), (state.clone(), d));
+					que.push_back(next);
+				}
+			}
+		}
+	}
+	None
+}
+
+/// Every ordering of `0..m`.
+fn orders(m: usize) -> Vec<Vec<usize>> {
+	if m == 0 {
+		return vec![vec![]];
+	}
+	let mut orders = vec![];
+	for order in self::orders(m - 1) {
+		for k in 0..m {
+			let mut order = order.clone();
+			order.insert(k, m - 1);
+			orders.push(order);
+		}
+	}
+	orders
+}
+
+fn dist((i1, j1): (usize, usize), (i2, j2): (usize, usize)) -> usize {
+	i1.abs_diff(i2) + j1.abs_diff(j2)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type Cell = (usize, usize);
+
+	/// Replays `moves` on an `n`x`n` board of distinct tiles with the empty square at `empty`,
+	/// checking that the empty square never leaves `region`. Returns the board in row-major
+	/// order, each cell holding the cell its tile started in, and where the empty square ended.
+	fn replay(n: usize, region: &Region, mut empty: Cell, moves: &[char]) -> (Vec<Cell>, Cell) {
+		let mut from = (0..n * n).map(|k| (k / n, k % n)).collect::<Vec<_>>();
+		for &c in moves {
+			let d = DIR.iter().position(|&d| d == c).unwrap();
+			let next = region.step(empty, d).unwrap_or_else(|| panic!("{} leaves the region at {:?}", c, empty));
+			from.swap(empty.0 * n + empty.1, next.0 * n + next.1);
+			empty = next;
+		}
+		(from, empty)
+	}
+
+	/// Checks that the tile at `moved[k].0` went to `moved[k].1` and every other tile stayed.
+	fn assert_moved(n: usize, region: &Region, empty: Cell, m: &Macro, moved: &[(Cell, Cell)]) {
+		let (from, end) = replay(n, region, empty, &m.moves);
+		assert_eq!(end, empty, "the empty square did not return");
+		for (k, &tile) in from.iter().enumerate() {
+			let cell = (k / n, k % n);
+			let expected = moved.iter().find(|&&(_, to)| to == cell).map_or(cell, |&(p, _)| p);
+			assert_eq!(tile, expected, "wrong tile at {:?}", cell);
+		}
+	}
+
+	#[test]
+	fn cycle3_moves_only_its_tiles() {
+		let region = Region::whole(5);
+		let (empty, cells) = ((4, 4), [(0, 0), (2, 3), (4, 1)]);
+		let m = cycle3(&region, empty, cells).unwrap();
+		assert_moved(5, &region, empty, &m, &[(cells[0], cells[1]), (cells[1], cells[2]), (cells[2], cells[0])]);
+	}
+
+	#[test]
+	fn cycle3_stays_in_a_narrow_region() {
+		let region = Region::new(4, 0, 6, 6);
+		let (empty, cells) = ((5, 5), [(4, 0), (5, 2), (4, 4)]);
+		let m = cycle3(&region, empty, cells).unwrap();
+		assert_moved(6, &region, empty, &m, &[(cells[0], cells[1]), (cells[1], cells[2]), (cells[2], cells[0])]);
+	}
+
+	#[test]
+	fn swap2_moves_only_its_tiles() {
+		let region = Region::whole(6);
+		let (empty, pairs) = ((2, 2), [((0, 0), (5, 5)), ((1, 4), (3, 1))]);
+		let m = swap2(&region, empty, pairs).unwrap();
+		let [(a, b), (c, d)] = pairs;
+		assert_moved(6, &region, empty, &m, &[(a, b), (b, a), (c, d), (d, c)]);
+	}
+
+	#[test]
+	fn odd_permutations_are_refused() {
+		assert_eq!(permute(&Region::whole(4), (3, 3), &[(0, 0), (1, 1)], &[(1, 1), (0, 0)]), None);
+	}
+}
@@ -0,0 +1,96 @@
+//! Traces of how a solver came to its answer, for the visualizer.
+//!
+//! A trace file has `key=value` lines: `target=` with the hex digits of the target board in
+//! row-major order, `step=I J START END` for each cell `(I, J)` the greedy router placed in turns
+//! `START..END` of its route, and `improvement=TIME SCORE MOVES` for each improvement of the
+//! annealing. Only the target may be left out; there is at most one.
+
+use crate::solver::Tiles;
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+	pub cell: (usize, usize),
+	/// Turns of the route that placed the tile of `cell`. Cells placed together share them.
+	pub turns: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Improvement {
+	/// Seconds on the clock of the budget.
+	pub time: f64,
+	/// The new best score, by the measure of the annealing.
+	pub score: f64,
+	pub moves: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+	pub target: Option<Tiles>,
+	/// Steps of the route to `target`, in the order they were made. The annealing may have
+	/// changed the route since.
+	pub steps: Vec<Step>,
+	pub improvements: Vec<Improvement>,
+}
+
+fn parse_numbers<T: std::str::FromStr>(key: &str, value: &str, count: usize) -> Result<Vec<T>, String> {
+	let numbers = value.split_whitespace().map(|v| v.parse().ok()).collect::<Option<Vec<T>>>();
+	match numbers {
+		Some(numbers) if numbers.len() == count => Ok(numbers),
+		_ => Err(format!("bad {}: {}", key, value)),
+	}
+}
+
+impl Trace {
+	pub fn write(&self, path: &str) -> std::io::Result<()> {
+		std::fs::write(path, self.to_string())
+	}
+
+	pub fn read(path: &str) -> Result<Trace, String> {
+		let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+		Trace::parse(&text).map_err(|err| format!("{}: {}", path, err))
+	}
+
+	pub fn parse(text: &str) -> Result<Trace, String> {
+		let mut trace = Trace::default();
+		for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+			let (key, value) = line.split_once('=').ok_or_else(|| format!("expected key=value: {}", line))?;
+			match key {
+				"target" => {
+					let tiles = value.chars().map(|c| c.to_digit(16).map(|tile| tile as usize)).collect::<Option<Vec<_>>>();
+					let n = (1..=value.len()).find(|n| n * n >= value.len()).unwrap_or(0);
+					match tiles {
+						Some(tiles) if n > 0 && n * n == tiles.len() => trace.target = Some(tiles.chunks(n).map(<[usize]>::to_vec).collect()),
+						_ => return Err(format!("bad target: {}", value)),
+					}
+				}
+				"step" => {
+					let v = parse_numbers::<usize>(key, value, 4)?;
+					trace.steps.push(Step { cell: (v[0], v[1]), turns: v[2]..v[3] });
+				}
+				"improvement" => {
+					let v = parse_numbers::<f64>(key, value, 3)?;
+					trace.improvements.push(Improvement { time: v[0], score: v[1], moves: v[2] as usize });
+				}
+				_ => return Err(format!("unknown key: {}", key)),
+			}
+		}
+		Ok(trace)
+	}
+}
+
+/// The trace as a file `Trace::parse` reads back.
+impl std::fmt::Display for Trace {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if let Some(target) = &self.target {
+			writeln!(f, "target={}", target.iter().flatten().map(|tile| format!("{:x}", tile)).collect::<String>())?;
+		}
+		for step in &self.steps {
+			writeln!(f, "step={} {} {} {}", step.cell.0, step.cell.1, step.turns.start, step.turns.end)?;
+		}
+		for improvement in &self.improvements {
+			writeln!(f, "improvement={} {} {}", improvement.time, improvement.score, improvement.moves)?;
+		}
+		Ok(())
+	}
+}
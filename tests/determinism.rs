@@ -0,0 +1,50 @@
+//! Runs on a counted budget must print the same answer every time.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const ARGS: [&str; 2] = ["--duration=1", "--evals_per_sec=20000"];
+
+fn run(exe: &str, args: &[&str], input: &str) -> String {
+	let mut child = Command::new(exe)
+		.args(ARGS)
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+	let output = child.wait_with_output().unwrap();
+	assert!(output.status.success(), "{} failed", exe);
+	String::from_utf8(output.stdout).unwrap()
+}
+
+fn assert_deterministic(exe: &str, args: &[&str]) {
+	for file in ["0000.txt", "0002.txt"] {
+		let input = std::fs::read_to_string(format!("{}/in/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap();
+		let first = run(exe, args, &input);
+		assert!(!first.trim().is_empty(), "{} printed nothing for {}", exe, file);
+		assert_eq!(first, run(exe, args, &input), "{} is not deterministic on {}", exe, file);
+	}
+}
+
+#[test]
+fn main_is_deterministic() {
+	assert_deterministic(env!("CARGO_BIN_EXE_main"), &[]);
+}
+
+#[test]
+fn solution_annealing_is_deterministic() {
+	assert_deterministic(env!("CARGO_BIN_EXE_solution_annealing"), &[]);
+}
+
+#[test]
+fn sequential_portfolio_is_deterministic() {
+	assert_deterministic(env!("CARGO_BIN_EXE_portfolio"), &["--sequential"]);
+}
+
+#[test]
+fn beam_search_is_deterministic() {
+	assert_deterministic(env!("CARGO_BIN_EXE_solution_beam_search"), &[]);
+}